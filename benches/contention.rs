@@ -0,0 +1,82 @@
+//! Acquires independent types from one core on separate threads, each behind a factory that takes
+//! `DELAY` to complete. Factories run outside the registry lock, so the concurrent case should
+//! take about one `DELAY` rather than one per type.
+
+use futures::{executor::block_on, future::ready};
+use std::{
+    any::Any,
+    convert::Infallible,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+use vessels::{acquire, register, Core, CoreFutureExt};
+
+const DELAY: Duration = Duration::from_millis(50);
+const ROUNDS: u32 = 5;
+
+async fn slow<T: Any + Default + Send>() {
+    register(|| {
+        thread::sleep(DELAY);
+        ready(Ok::<_, Infallible>(T::default()))
+    })
+    .await
+    .unwrap()
+}
+
+fn acquire_on<T: Any + Send>(core: Arc<Core>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let item = block_on(async { acquire::<T>().await }.in_core(&core));
+        assert!(item.unwrap().is_some());
+    })
+}
+
+macro_rules! types {
+    ($($ty:ty),*) => {
+        fn core() -> Arc<Core> {
+            let core = Core::new();
+            block_on(async { $(slow::<$ty>().await;)* }.in_core(&core));
+            Arc::new(core)
+        }
+
+        fn sequential(core: &Arc<Core>) {
+            $(acquire_on::<$ty>(core.clone()).join().unwrap();)*
+        }
+
+        fn concurrent(core: &Arc<Core>) {
+            let handles = vec![$(acquire_on::<$ty>(core.clone())),*];
+
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        }
+
+        const TYPES: u32 = [$(stringify!($ty)),*].len() as u32;
+    };
+}
+
+types!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+fn time(name: &str, run: fn(&Arc<Core>)) {
+    let core = core();
+    let start = Instant::now();
+
+    for _ in 0..ROUNDS {
+        run(&core);
+    }
+
+    let elapsed = start.elapsed() / ROUNDS;
+
+    println!(
+        "{:<12} {:>4} types  {:>8.1?} per round  ({:.1} delays)",
+        name,
+        TYPES,
+        elapsed,
+        elapsed.as_secs_f64() / DELAY.as_secs_f64()
+    );
+}
+
+fn main() {
+    time("sequential", sequential);
+    time("concurrent", concurrent);
+}
@@ -0,0 +1,46 @@
+//! Hashes inputs of increasing size through `Ring` in fixed chunks, reporting throughput and the
+//! process's peak resident memory. `Ring` keeps only a digest context, so the peak should stay
+//! flat as the input grows.
+
+use std::{fs, time::Instant};
+use vessels::{resource::hash::Hasher, Ring, Sha256};
+
+const CHUNK: usize = 64 * 1024;
+const SIZES: [usize; 4] = [16 << 20, 64 << 20, 256 << 20, 1 << 30];
+
+// peak resident set size in KiB, where the platform reports it
+fn peak() -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with("VmHWM:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()
+}
+
+fn main() {
+    let chunk = vec![0x5a; CHUNK];
+
+    for size in SIZES.iter() {
+        let start = Instant::now();
+        let mut hasher = <Ring as Hasher<Sha256>>::new();
+
+        for _ in 0..size / CHUNK {
+            hasher.write(&chunk);
+        }
+
+        hasher.hash();
+
+        let elapsed = start.elapsed();
+
+        println!(
+            "{:>5} MiB  {:>8.1?}  {:>7.1} MiB/s  peak {}",
+            size >> 20,
+            elapsed,
+            (size >> 20) as f64 / elapsed.as_secs_f64(),
+            peak().map_or("unavailable".to_owned(), |peak| format!("{} KiB", peak))
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use crate::{Core, CoreError, CoreFutureExt, Key, Lifetime, Singleton};
+use core::{
+    any::{type_name, Any, TypeId},
+    convert::Infallible,
+    future::Future,
+    pin::Pin,
+};
+use core_error::Error;
+use futures::{future::ready, stream::iter, TryFutureExt, TryStreamExt};
+use std::{collections::HashMap, sync::Arc};
+
+const DEFAULT_PARALLELISM: usize = 8;
+
+type Pending = Pin<Box<dyn Future<Output = Result<(), CoreError>> + Send>>;
+
+pub struct Dependency {
+    key: Key,
+    ty: &'static str,
+}
+
+impl Dependency {
+    /// Depends on an item acquired as `T`, such as one added with `factory` or `value`.
+    pub fn on<T: Any>() -> Self {
+        Dependency {
+            key: (TypeId::of::<T>(), None),
+            ty: type_name::<T>(),
+        }
+    }
+
+    /// Depends on a service added with `service::<T>`, which is acquired as `Arc<T>`.
+    pub fn on_service<T: Any>() -> Self {
+        Self::on::<Arc<T>>()
+    }
+}
+
+struct Service {
+    key: Key,
+    ty: &'static str,
+    dependencies: Vec<Dependency>,
+    register: Box<dyn FnOnce(&Singleton) -> Pending + Send>,
+    initialize: Option<fn(&Singleton) -> Pending>,
+}
+
+fn initialize<T: Any + Send + Sync>(singleton: &Singleton) -> Pending {
+    Box::pin(singleton.acquire::<Arc<T>>(None).map_ok(|_| ()))
+}
+
+/// Assembles a `Core` from services with declared dependencies. `build` rejects missing
+/// dependencies and cycles before anything is constructed, then eagerly initializes every
+/// `Lifetime::Singleton` service so that dependencies always complete before their dependents.
+pub struct CoreBuilder {
+    services: Vec<Service>,
+    parallelism: usize,
+}
+
+impl Core {
+    pub fn builder() -> CoreBuilder {
+        CoreBuilder {
+            services: vec![],
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+impl CoreBuilder {
+    fn push(&mut self, service: Service) {
+        if let Some(existing) = self.services.iter_mut().find(|s| s.key == service.key) {
+            *existing = service;
+        } else {
+            self.services.push(service);
+        }
+    }
+
+    /// Adds a service acquired as `Arc<T>`, as with `register_with`.
+    pub fn service<
+        T: Any + Send + Sync,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    >(
+        mut self,
+        lifetime: Lifetime,
+        dependencies: Vec<Dependency>,
+        cb: F,
+    ) -> Self {
+        self.push(Service {
+            key: (TypeId::of::<Arc<T>>(), None),
+            ty: type_name::<Arc<T>>(),
+            dependencies,
+            register: Box::new(move |singleton| {
+                Box::pin(singleton.register_with(None, false, lifetime, cb))
+            }),
+            initialize: if lifetime == Lifetime::Singleton {
+                Some(initialize::<T>)
+            } else {
+                None
+            },
+        });
+        self
+    }
+
+    /// Adds a plain value, acquired as a clone of `value` by `acquire::<T>()`.
+    pub fn value<T: Any + Clone + Send + Sync>(self, value: T) -> Self {
+        self.factory(move || ready(Ok::<_, Infallible>(value.clone())))
+    }
+
+    /// Adds a transient factory for `T` without declared dependencies, as with `register`.
+    pub fn factory<
+        T: Any + Send,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    >(
+        mut self,
+        cb: F,
+    ) -> Self {
+        self.push(Service {
+            key: (TypeId::of::<T>(), None),
+            ty: type_name::<T>(),
+            dependencies: vec![],
+            register: Box::new(move |singleton| Box::pin(singleton.register(None, false, cb))),
+            initialize: None,
+        });
+        self
+    }
+
+    /// Limits how many services of the same depth are initialized concurrently.
+    pub fn parallelism(mut self, limit: usize) -> Self {
+        self.parallelism = limit.max(1);
+        self
+    }
+
+    fn layers(&self) -> Result<Vec<Vec<usize>>, CoreError> {
+        let index = self
+            .services
+            .iter()
+            .enumerate()
+            .map(|(i, service)| (&service.key, i))
+            .collect::<HashMap<_, _>>();
+
+        let mut depths = vec![None; self.services.len()];
+        let mut stack = vec![];
+
+        for i in 0..self.services.len() {
+            self.depth(i, &index, &mut depths, &mut stack)?;
+        }
+
+        let mut layers: Vec<Vec<usize>> = vec![];
+
+        for (i, depth) in depths.into_iter().enumerate() {
+            let depth = depth.unwrap();
+
+            if layers.len() <= depth {
+                layers.resize(depth + 1, vec![]);
+            }
+
+            layers[depth].push(i);
+        }
+
+        Ok(layers)
+    }
+
+    fn depth(
+        &self,
+        i: usize,
+        index: &HashMap<&Key, usize>,
+        depths: &mut [Option<usize>],
+        stack: &mut Vec<usize>,
+    ) -> Result<usize, CoreError> {
+        if let Some(depth) = depths[i] {
+            return Ok(depth);
+        }
+
+        if let Some(position) = stack.iter().position(|j| *j == i) {
+            let mut cycle = stack[position..]
+                .iter()
+                .map(|j| self.services[*j].ty)
+                .collect::<Vec<_>>();
+            cycle.push(self.services[i].ty);
+            return Err(CoreError::Cycle(cycle));
+        }
+
+        stack.push(i);
+
+        let mut depth = 0;
+
+        for dependency in &self.services[i].dependencies {
+            let j = *index
+                .get(&dependency.key)
+                .ok_or(CoreError::NotRegistered(dependency.ty))?;
+
+            depth = depth.max(self.depth(j, index, depths, stack)? + 1);
+        }
+
+        stack.pop();
+        depths[i] = Some(depth);
+
+        Ok(depth)
+    }
+
+    pub fn build(self) -> impl Future<Output = Result<Core, CoreError>> {
+        async move {
+            let layers = self.layers()?;
+            let core = Core::new();
+
+            let mut initializers = vec![];
+
+            for service in self.services {
+                (service.register)(&core.singleton).await?;
+                initializers.push(service.initialize);
+            }
+
+            for layer in layers {
+                iter(
+                    layer
+                        .into_iter()
+                        .filter_map(|i| initializers[i])
+                        .map(|initialize| Ok(initialize(&core.singleton))),
+                )
+                .try_buffer_unordered(self.parallelism)
+                .try_for_each(|_| ready(Ok(())))
+                .await?;
+            }
+
+            Ok(core)
+        }
+    }
+
+    /// Builds the core and drives `body` to completion with it installed as the current core,
+    /// which makes for single-expression setup in tests.
+    pub fn run<F: Future>(self, body: F) -> impl Future<Output = Result<F::Output, CoreError>> {
+        async move {
+            let core = self.build().await?;
+
+            Ok(body.in_core(&core).await)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::require;
+    use core::task::Poll;
+    use futures::{executor::block_on, future::poll_fn};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Db;
+
+    struct Api(Arc<Db>);
+
+    #[test]
+    fn services_depend_on_services() {
+        let api = block_on(
+            Core::builder()
+                .service(Lifetime::Singleton, vec![], || {
+                    ready(Ok::<_, Infallible>(Db))
+                })
+                .service(
+                    Lifetime::Singleton,
+                    vec![Dependency::on_service::<Db>()],
+                    || async { Ok::<_, CoreError>(Api(require::<Arc<Db>>().await?)) },
+                )
+                .run(async { require::<Arc<Api>>().await }),
+        );
+
+        assert!(api.unwrap().is_ok());
+    }
+
+    #[test]
+    fn missing_dependencies_are_rejected() {
+        let core = block_on(
+            Core::builder()
+                .service(Lifetime::Singleton, vec![Dependency::on::<Db>()], || {
+                    ready(Ok::<_, Infallible>(Db))
+                })
+                .build(),
+        );
+
+        match core {
+            Err(CoreError::NotRegistered(ty)) => assert!(ty.ends_with("Db")),
+            _ => panic!("expected a missing dependency"),
+        }
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let constructed = Arc::new(AtomicUsize::new(0));
+        let (db, api) = (constructed.clone(), constructed.clone());
+
+        let core = block_on(
+            Core::builder()
+                .service(
+                    Lifetime::Singleton,
+                    vec![Dependency::on_service::<Api>()],
+                    move || {
+                        db.fetch_add(1, Ordering::SeqCst);
+                        ready(Ok::<_, Infallible>(Db))
+                    },
+                )
+                .service(
+                    Lifetime::Singleton,
+                    vec![Dependency::on_service::<Db>()],
+                    move || {
+                        api.fetch_add(1, Ordering::SeqCst);
+                        async { Ok::<_, CoreError>(Api(require::<Arc<Db>>().await?)) }
+                    },
+                )
+                .build(),
+        );
+
+        match core {
+            Err(CoreError::Cycle(cycle)) => assert_eq!(
+                cycle,
+                vec![
+                    type_name::<Arc<Db>>(),
+                    type_name::<Arc<Api>>(),
+                    type_name::<Arc<Db>>()
+                ]
+            ),
+            _ => panic!("expected a cycle"),
+        }
+        assert_eq!(constructed.load(Ordering::SeqCst), 0);
+    }
+
+    // adds a singleton that stays under construction across one poll, recording how many are
+    // under construction at once
+    fn tracked<T: Any + Default + Send + Sync>(
+        builder: CoreBuilder,
+        active: &Arc<AtomicUsize>,
+        peak: &Arc<AtomicUsize>,
+    ) -> CoreBuilder {
+        let (active, peak) = (active.clone(), peak.clone());
+
+        builder.service(Lifetime::Singleton, vec![], move || {
+            let (active, peak) = (active.clone(), peak.clone());
+            let mut yielded = false;
+
+            async move {
+                peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+
+                poll_fn(|context| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        context.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+
+                active.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, Infallible>(T::default())
+            }
+        })
+    }
+
+    fn peak(builder: CoreBuilder) -> usize {
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let builder = tracked::<u8>(builder, &active, &peak);
+        let builder = tracked::<u16>(builder, &active, &peak);
+        let builder = tracked::<u32>(builder, &active, &peak);
+        let builder = tracked::<u64>(builder, &active, &peak);
+
+        block_on(builder.build()).unwrap();
+
+        peak.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn initialization_is_limited_by_parallelism() {
+        assert_eq!(peak(Core::builder()), 4);
+        assert_eq!(peak(Core::builder().parallelism(2)), 2);
+        assert_eq!(peak(Core::builder().parallelism(0)), 1);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Config(&'static str);
+
+    #[test]
+    fn values_are_mocked_in_one_line() {
+        let config = block_on(
+            Core::builder()
+                .value(Config("test"))
+                .run(async { require::<Config>().await }),
+        );
+
+        assert_eq!(config.unwrap().unwrap(), Config("test"));
+    }
+
+    #[test]
+    fn factories_run_on_every_acquisition() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory = count.clone();
+
+        let items = block_on(
+            Core::builder()
+                .factory(move || ready(Ok::<_, Infallible>(factory.fetch_add(1, Ordering::SeqCst))))
+                .value(Config("replaced"))
+                .value(Config("test"))
+                .run(async {
+                    Ok::<_, CoreError>((
+                        require::<usize>().await?,
+                        require::<usize>().await?,
+                        require::<Config>().await?,
+                    ))
+                }),
+        );
+
+        assert_eq!(items.unwrap().unwrap(), (0, 1, Config("test")));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+}
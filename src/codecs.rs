@@ -0,0 +1,146 @@
+use crate::resource::{Chunks, Rehydrate, StreamingRehydrate};
+use futures::{
+    future::{ready, FutureExt, Map, Ready},
+    stream::{once, Concat, Once, StreamExt},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+macro_rules! serde_codec {
+    ($codec:ident, $feature:literal, $rehydrate_error:ty, $dump_error:ty, $from:expr, $to:expr) => {
+        #[cfg(feature = $feature)]
+        pub struct $codec;
+
+        #[cfg(feature = $feature)]
+        impl<T: DeserializeOwned + Serialize> Rehydrate<T> for $codec {
+            type RehydrateError = $rehydrate_error;
+            type Rehydrate = Ready<Result<T, Self::RehydrateError>>;
+            type DumpError = $dump_error;
+            type Dump = Ready<Result<Vec<u8>, Self::DumpError>>;
+
+            fn rehydrate(data: Vec<u8>) -> Self::Rehydrate {
+                ready($from(&data))
+            }
+            fn dump(data: T) -> Self::Dump {
+                ready($to(&data))
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T: DeserializeOwned + Serialize> StreamingRehydrate<T> for $codec {
+            type RehydrateStream = Map<Concat<Chunks>, fn(Vec<u8>) -> Result<T, $rehydrate_error>>;
+            type DumpStream = Once<Ready<Result<Vec<u8>, $dump_error>>>;
+
+            fn rehydrate_stream(data: Chunks) -> Self::RehydrateStream {
+                data.concat().map(|data| $from(&data))
+            }
+            fn dump_stream(data: T) -> Self::DumpStream {
+                once(ready($to(&data)))
+            }
+        }
+    };
+}
+
+serde_codec!(
+    Json,
+    "json",
+    serde_json::Error,
+    serde_json::Error,
+    serde_json::from_slice,
+    serde_json::to_vec
+);
+
+serde_codec!(
+    Bincode,
+    "bincode",
+    bincode::Error,
+    bincode::Error,
+    bincode::deserialize,
+    bincode::serialize
+);
+
+serde_codec!(
+    MessagePack,
+    "messagepack",
+    rmp_serde::decode::Error,
+    rmp_serde::encode::Error,
+    rmp_serde::from_slice,
+    rmp_serde::to_vec_named
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Debug;
+    use futures::{executor::block_on, stream::iter};
+    use std::collections::HashMap;
+
+    type Item = (String, u32, Vec<Option<u8>>, HashMap<String, bool>);
+
+    fn item() -> Item {
+        let mut flags = HashMap::new();
+        flags.insert("enabled".to_owned(), true);
+
+        ("vessel".to_owned(), 7, vec![Some(1), None], flags)
+    }
+
+    fn round_trip<U: StreamingRehydrate<Item>>()
+    where
+        U::RehydrateError: Debug,
+        U::DumpError: Debug,
+    {
+        block_on(async {
+            let data = U::dump(item()).await.unwrap();
+            assert_eq!(U::rehydrate(data.clone()).await.unwrap(), item());
+
+            let chunks = data.chunks(3).map(<[u8]>::to_vec).collect::<Vec<_>>();
+            let item = U::rehydrate_stream(Box::pin(iter(chunks))).await;
+            assert_eq!(item.unwrap(), self::item());
+        });
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trips() {
+        round_trip::<Json>();
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode_round_trips() {
+        round_trip::<Bincode>();
+    }
+
+    #[cfg(feature = "messagepack")]
+    #[test]
+    fn messagepack_round_trips() {
+        round_trip::<MessagePack>();
+    }
+
+    #[cfg(all(feature = "json", feature = "messagepack"))]
+    #[test]
+    fn codecs_reject_each_others_data() {
+        use crate::{
+            resource::{manager::ResourceManagerExt, ResourceError},
+            MemoryStore, PortableSha256, Resource, Sha256, SimpleResourceManager,
+        };
+
+        block_on(async {
+            let mut store = MemoryStore::<Sha256>::new();
+            let written: Resource<Item, Json, Sha256> =
+                store.intern::<PortableSha256, _, _>(item()).await.unwrap();
+
+            let mut manager = SimpleResourceManager::new();
+            manager
+                .register_streaming_provider::<Sha256, _>(store)
+                .await
+                .unwrap();
+
+            let read = Resource::<Item, MessagePack, Sha256>::new(written.hash());
+
+            assert!(matches!(
+                manager.fetch(read).await,
+                Err(ResourceError::Rehydration(_))
+            ));
+        });
+    }
+}
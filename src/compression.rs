@@ -0,0 +1,302 @@
+use crate::resource::Rehydrate;
+use core::marker::PhantomData;
+use core_error::Error;
+use futures::{
+    future::{ready, Either, FutureExt, Map, MapErr, Ready},
+    TryFutureExt,
+};
+use std::io;
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+use std::io::Read;
+#[cfg(feature = "deflate")]
+use std::io::Write;
+use thiserror::Error;
+
+const STORED: u8 = 0;
+#[cfg(feature = "deflate")]
+const DEFLATE: u8 = 1;
+#[cfg(feature = "deflate")]
+const GZIP: u8 = 2;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 3;
+
+/// Largest output decompression will produce by default, as data from an untrusted provider could
+/// otherwise expand without bound.
+pub const MAX_DECOMPRESSED_SIZE: usize = 256 * 1024 * 1024;
+
+/// Bounds how far `Compressed` will let data expand when decompressing it. Data that is merely
+/// stored is never subject to the limit, since it doesn't expand.
+pub trait Limit {
+    const BYTES: usize;
+}
+
+/// Limits decompression to `MAX_DECOMPRESSED_SIZE`.
+pub struct DefaultLimit;
+
+impl Limit for DefaultLimit {
+    const BYTES: usize = MAX_DECOMPRESSED_SIZE;
+}
+
+/// Lifts the limit entirely, for data from trusted providers only.
+pub struct Unlimited;
+
+impl Limit for Unlimited {
+    const BYTES: usize = usize::MAX;
+}
+
+/// A compression method for `Compressed`. Each method writes its own `METHOD` byte ahead of the
+/// data, and decompression goes by that byte rather than by the `Compressor` in the type, so
+/// changing the method used for new resources doesn't strand existing ones.
+pub trait Compressor {
+    const METHOD: u8;
+
+    fn compress(data: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+pub struct Stored;
+
+impl Compressor for Stored {
+    const METHOD: u8 = STORED;
+
+    fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+#[cfg(feature = "deflate")]
+pub struct Deflate;
+
+#[cfg(feature = "deflate")]
+impl Compressor for Deflate {
+    const METHOD: u8 = DEFLATE;
+
+    fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+}
+
+#[cfg(feature = "deflate")]
+pub struct Gzip;
+
+#[cfg(feature = "deflate")]
+impl Compressor for Gzip {
+    const METHOD: u8 = GZIP;
+
+    fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Compressor for Zstd {
+    const METHOD: u8 = ZSTD;
+
+    fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+        zstd::encode_all(data, 0)
+    }
+}
+
+#[derive(Debug, Error)]
+#[bounds(where T: Error + 'static)]
+pub enum CompressionError<T> {
+    #[error("codec error: {0}")]
+    Codec(#[source] T),
+    #[error("compression error: {0}")]
+    Io(#[source] io::Error),
+    #[error("unsupported compression method {0}")]
+    Method(u8),
+    #[error("missing compression header")]
+    Header,
+    #[error("data decompresses to more than {0} bytes")]
+    TooLarge(usize),
+}
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+fn read_limited<R: Read, T>(reader: R, limit: usize) -> Result<Vec<u8>, CompressionError<T>> {
+    let mut buffer = vec![];
+
+    reader
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut buffer)
+        .map_err(CompressionError::Io)?;
+
+    if buffer.len() > limit {
+        return Err(CompressionError::TooLarge(limit));
+    }
+
+    Ok(buffer)
+}
+
+// only expanding methods are subject to the limit, and there may be none of them enabled
+#[cfg_attr(
+    not(any(feature = "deflate", feature = "zstd")),
+    allow(unused_variables)
+)]
+fn decompress<T>(data: &[u8], limit: usize) -> Result<Vec<u8>, CompressionError<T>> {
+    let (method, data) = data.split_first().ok_or(CompressionError::Header)?;
+
+    match *method {
+        STORED => Ok(data.to_vec()),
+        #[cfg(feature = "deflate")]
+        DEFLATE => read_limited(flate2::read::DeflateDecoder::new(data), limit),
+        #[cfg(feature = "deflate")]
+        GZIP => read_limited(flate2::read::GzDecoder::new(data), limit),
+        #[cfg(feature = "zstd")]
+        ZSTD => read_limited(
+            zstd::stream::read::Decoder::new(data).map_err(CompressionError::Io)?,
+            limit,
+        ),
+        method => Err(CompressionError::Method(method)),
+    }
+}
+
+fn compress<C: Compressor, T>(data: Result<Vec<u8>, T>) -> Result<Vec<u8>, CompressionError<T>> {
+    let data = data.map_err(CompressionError::Codec)?;
+    let mut compressed = vec![C::METHOD];
+    compressed.extend(C::compress(&data).map_err(CompressionError::Io)?);
+    Ok(compressed)
+}
+
+/// Wraps the codec `U`, compressing what it dumps with `C` and refusing to decompress past `L`.
+pub struct Compressed<U, C, L = DefaultLimit>(PhantomData<(U, C, L)>);
+
+impl<T, U: Rehydrate<T>, C: Compressor, L: Limit> Rehydrate<T> for Compressed<U, C, L> {
+    type RehydrateError = CompressionError<U::RehydrateError>;
+    type Rehydrate = Either<
+        MapErr<U::Rehydrate, fn(U::RehydrateError) -> Self::RehydrateError>,
+        Ready<Result<T, Self::RehydrateError>>,
+    >;
+    type DumpError = CompressionError<U::DumpError>;
+    type Dump = Map<U::Dump, fn(Result<Vec<u8>, U::DumpError>) -> Result<Vec<u8>, Self::DumpError>>;
+
+    fn rehydrate(data: Vec<u8>) -> Self::Rehydrate {
+        match decompress(&data, L::BYTES) {
+            Ok(data) => {
+                Either::Left(U::rehydrate(data).map_err(
+                    CompressionError::Codec as fn(U::RehydrateError) -> Self::RehydrateError,
+                ))
+            }
+            Err(e) => Either::Right(ready(Err(e))),
+        }
+    }
+    fn dump(data: T) -> Self::Dump {
+        U::dump(data).map(compress::<C, U::DumpError>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cbor;
+    use futures::executor::block_on;
+
+    type Item = Vec<(String, u32)>;
+
+    fn item() -> Item {
+        (0..100).map(|i| (format!("entry {}", i % 10), i)).collect()
+    }
+
+    fn dump<C: Compressor>() -> Vec<u8> {
+        block_on(Compressed::<Cbor, C>::dump(item())).unwrap()
+    }
+
+    fn rehydrate<C: Compressor>(
+        data: Vec<u8>,
+    ) -> Result<Item, CompressionError<serde_cbor::Error>> {
+        block_on(Compressed::<Cbor, C>::rehydrate(data))
+    }
+
+    #[test]
+    fn stored_round_trips() {
+        assert_eq!(rehydrate::<Stored>(dump::<Stored>()).unwrap(), item());
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_round_trips() {
+        let data = dump::<Deflate>();
+
+        assert_eq!(data[0], DEFLATE);
+        assert!(data.len() < dump::<Stored>().len());
+        assert_eq!(rehydrate::<Deflate>(data).unwrap(), item());
+        assert_eq!(rehydrate::<Gzip>(dump::<Gzip>()).unwrap(), item());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trips() {
+        assert_eq!(rehydrate::<Zstd>(dump::<Zstd>()).unwrap(), item());
+    }
+
+    #[cfg(all(feature = "deflate", feature = "zstd"))]
+    #[test]
+    fn decompression_follows_the_header() {
+        assert_eq!(rehydrate::<Zstd>(dump::<Gzip>()).unwrap(), item());
+        assert_eq!(rehydrate::<Stored>(dump::<Deflate>()).unwrap(), item());
+    }
+
+    #[test]
+    fn unknown_methods_are_rejected() {
+        assert!(matches!(
+            rehydrate::<Stored>(vec![0xff, 0]),
+            Err(CompressionError::Method(0xff))
+        ));
+        assert!(matches!(
+            rehydrate::<Stored>(vec![]),
+            Err(CompressionError::Header)
+        ));
+    }
+
+    #[test]
+    fn stored_data_is_not_limited() {
+        let data = [&[STORED][..], &[0u8; 1 << 16][..]].concat();
+
+        assert_eq!(decompress::<()>(&data, 1 << 15).unwrap().len(), 1 << 16);
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn limits_are_configurable() {
+        struct Small;
+
+        impl Limit for Small {
+            const BYTES: usize = 64;
+        }
+
+        let data = dump::<Deflate>();
+
+        assert!(matches!(
+            block_on(
+                <Compressed<Cbor, Deflate, Small> as Rehydrate<Item>>::rehydrate(data.clone())
+            ),
+            Err(CompressionError::TooLarge(64))
+        ));
+        assert_eq!(
+            block_on(<Compressed<Cbor, Deflate, Unlimited> as Rehydrate<Item>>::rehydrate(data))
+                .unwrap(),
+            item()
+        );
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn decompressed_size_is_limited() {
+        let mut data = vec![DEFLATE];
+        data.extend(Deflate::compress(&[0u8; 1 << 16]).unwrap());
+
+        assert!(data.len() < 1024);
+        assert_eq!(decompress::<()>(&data, 1 << 16).unwrap().len(), 1 << 16);
+        assert!(matches!(
+            decompress::<()>(&data, 1 << 15),
+            Err(CompressionError::TooLarge(_))
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+use core::{
+    fmt::{self, Formatter},
+    marker::PhantomData,
+    str::FromStr,
+};
+use serde::de::{Error, SeqAccess, Visitor};
+use thiserror::Error;
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseHashError {
+    #[error("{len} characters is not a valid length for a {expected} byte digest")]
+    Length { len: usize, expected: usize },
+    #[error("invalid character {0:?} in digest")]
+    Character(char),
+    #[error("non-zero trailing bits in base32 digest")]
+    Trailing,
+    #[error("expected a vessel: URI")]
+    Scheme,
+    #[error("expected a {expected} hash, found {found}")]
+    Algorithm {
+        expected: &'static str,
+        found: String,
+    },
+}
+
+pub fn hex(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len() * 2);
+
+    for byte in data {
+        encoded.push(HEX[(byte >> 4) as usize] as char);
+        encoded.push(HEX[(byte & 0xf) as usize] as char);
+    }
+
+    encoded
+}
+
+/// Unpadded lowercase RFC 4648 base32.
+pub fn base32(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity((data.len() * 8 + 4) / 5);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | u16::from(*byte);
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(BASE32[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    encoded
+}
+
+fn value(character: char, alphabet: &[u8]) -> Result<u16, ParseHashError> {
+    let lower = character.to_ascii_lowercase();
+
+    alphabet
+        .iter()
+        .position(|c| *c as char == lower)
+        .map(|position| position as u16)
+        .ok_or(ParseHashError::Character(character))
+}
+
+/// Decodes either encoding into `output`, telling them apart by length.
+pub fn decode(input: &str, output: &mut [u8]) -> Result<(), ParseHashError> {
+    let len = input.chars().count();
+
+    if len == output.len() * 2 {
+        let mut characters = input.chars();
+
+        for byte in output.iter_mut() {
+            let high = value(characters.next().unwrap(), HEX)?;
+            let low = value(characters.next().unwrap(), HEX)?;
+            *byte = (high << 4 | low) as u8;
+        }
+    } else if len == (output.len() * 8 + 4) / 5 {
+        let mut buffer = 0u16;
+        let mut bits = 0;
+        let mut position = 0;
+
+        for character in input.chars() {
+            buffer = (buffer << 5) | value(character, BASE32)?;
+            bits += 5;
+
+            if bits >= 8 {
+                bits -= 8;
+                output[position] = (buffer >> bits) as u8;
+                position += 1;
+            }
+        }
+
+        // otherwise several strings would decode to the same digest
+        if buffer & ((1 << bits) - 1) != 0 {
+            return Err(ParseHashError::Trailing);
+        }
+    } else {
+        return Err(ParseHashError::Length {
+            len,
+            expected: output.len(),
+        });
+    }
+
+    Ok(())
+}
+
+pub trait FixedDigest: FromStr<Err = ParseHashError> {
+    const LEN: usize;
+
+    fn from_slice(data: &[u8]) -> Self;
+}
+
+/// Accepts a digest as a string in human-readable formats and as raw bytes otherwise.
+pub struct DigestVisitor<T>(pub PhantomData<T>);
+
+impl<'de, T: FixedDigest> Visitor<'de> for DigestVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "a {} byte digest", T::LEN)
+    }
+
+    fn visit_str<E: Error>(self, data: &str) -> Result<T, E> {
+        data.parse().map_err(E::custom)
+    }
+
+    fn visit_bytes<E: Error>(self, data: &[u8]) -> Result<T, E> {
+        if data.len() != T::LEN {
+            return Err(E::invalid_length(data.len(), &self));
+        }
+
+        Ok(T::from_slice(data))
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<T, S::Error> {
+        let mut data = vec![];
+
+        while let Some(byte) = seq.next_element()? {
+            data.push(byte);
+        }
+
+        self.visit_bytes(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-256 of "abc"
+    const HEX: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const BASE32: &str = "xj4bnp4pahh6uqkbidpf3lrceoyagyndsylxvhfucd7wd4qacwwq";
+
+    fn parse(input: &str) -> Result<[u8; 32], ParseHashError> {
+        let mut output = [0u8; 32];
+        decode(input, &mut output).map(|_| output)
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let digest = parse(HEX).unwrap();
+
+        assert_eq!(hex(&digest), HEX);
+        assert_eq!(base32(&digest), BASE32);
+        assert_eq!(parse(BASE32).unwrap(), digest);
+        assert_eq!(parse(&BASE32.to_uppercase()).unwrap(), digest);
+    }
+
+    #[test]
+    fn non_canonical_base32_is_rejected() {
+        let mut input = BASE32.to_owned();
+        input.pop();
+        input.push('r');
+
+        assert_eq!(parse(&input), Err(ParseHashError::Trailing));
+    }
+
+    #[test]
+    fn malformed_digests_are_rejected() {
+        assert_eq!(
+            parse(&HEX[1..]),
+            Err(ParseHashError::Length {
+                len: 63,
+                expected: 32
+            })
+        );
+        assert_eq!(
+            parse(&HEX.replace('f', "g")),
+            Err(ParseHashError::Character('g'))
+        );
+        assert_eq!(
+            parse(&BASE32.replace('x', "1")),
+            Err(ParseHashError::Character('1'))
+        );
+    }
+}
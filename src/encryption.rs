@@ -0,0 +1,434 @@
+use crate::{acquire, acquire_named, resource::Rehydrate, CoreError};
+use core::{
+    fmt::{self, Debug, Formatter},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+};
+use core_error::Error;
+use futures::future::ready;
+use ring::{
+    aead::{
+        Aad, Algorithm, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, CHACHA20_POLY1305, NONCE_LEN,
+    },
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::sync::Arc;
+use thiserror::Error;
+
+const VERSION: u8 = 1;
+const CHECK_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    fn code(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 1,
+            Cipher::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(Cipher::Aes256Gcm),
+            2 => Some(Cipher::ChaCha20Poly1305),
+            _ => None,
+        }
+    }
+
+    fn algorithm(self) -> &'static Algorithm {
+        match self {
+            Cipher::Aes256Gcm => &AES_256_GCM,
+            Cipher::ChaCha20Poly1305 => &CHACHA20_POLY1305,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum InvalidKey {
+    #[error("{cipher:?} keys must be {expected} bytes, not {len}")]
+    Length {
+        cipher: Cipher,
+        expected: usize,
+        len: usize,
+    },
+    #[error("key ids must be at most 255 bytes")]
+    Id,
+}
+
+/// An AEAD key for `Encrypted`, acquired from the current `Core`. New data is encrypted with the
+/// unnamed `Key` registration; data is decrypted with the key registered under the id recorded
+/// in its envelope, or the unnamed key if that has the same id. Register retired keys by id with
+/// `register_named` to keep older data readable after rotation. Keys may be registered either as
+/// `Key` or, through `register_with` and `register_named_with`, as `Arc<Key>`.
+#[derive(Clone)]
+pub struct Key {
+    id: String,
+    cipher: Cipher,
+    bytes: Vec<u8>,
+}
+
+impl Debug for Key {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Key")
+            .field("id", &self.id)
+            .field("cipher", &self.cipher)
+            .finish()
+    }
+}
+
+impl Key {
+    pub fn new<K: Into<String>>(id: K, cipher: Cipher, bytes: Vec<u8>) -> Result<Self, InvalidKey> {
+        let id = id.into();
+        let expected = cipher.algorithm().key_len();
+
+        if bytes.len() != expected {
+            return Err(InvalidKey::Length {
+                cipher,
+                expected,
+                len: bytes.len(),
+            });
+        }
+
+        if id.len() > 255 {
+            return Err(InvalidKey::Id);
+        }
+
+        Ok(Key { id, cipher, bytes })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn check(&self) -> [u8; CHECK_LEN] {
+        let tag = hmac::sign(
+            &hmac::Key::new(hmac::HMAC_SHA256, &self.bytes),
+            &[b"vessels key check".as_ref(), &[self.cipher.code()]].concat(),
+        );
+        let mut check = [0u8; CHECK_LEN];
+        check.copy_from_slice(&tag.as_ref()[..CHECK_LEN]);
+        check
+    }
+
+    fn aead(&self) -> LessSafeKey {
+        LessSafeKey::new(UnboundKey::new(self.cipher.algorithm(), &self.bytes).unwrap())
+    }
+}
+
+#[derive(Debug, Error)]
+#[bounds(where T: Error + 'static)]
+pub enum EncryptionError<T> {
+    #[error("codec error: {0}")]
+    Codec(#[source] T),
+    #[error("failed to acquire key: {0}")]
+    Core(#[source] CoreError),
+    #[error("no key registered for id {0:?}")]
+    MissingKey(Option<String>),
+    #[error("data was encrypted with a different key than the one registered as {0:?}")]
+    WrongKey(String),
+    #[error("encrypted data failed authentication")]
+    Tampered,
+    #[error("malformed encryption envelope")]
+    Malformed,
+    #[error("cryptographic operation failed")]
+    Crypto,
+}
+
+// looks a key up as registered with `register` and then as shared by `register_with`
+async fn find(id: Option<String>) -> Result<Option<Key>, CoreError> {
+    let key = match &id {
+        Some(id) => acquire_named::<Key, _>(id.clone()).await?,
+        None => acquire::<Key>().await?,
+    };
+
+    if key.is_some() {
+        return Ok(key);
+    }
+
+    let key = match id {
+        Some(id) => acquire_named::<Arc<Key>, _>(id).await?,
+        None => acquire::<Arc<Key>>().await?,
+    };
+
+    Ok(key.map(|key| (*key).clone()))
+}
+
+struct Header {
+    cipher: Cipher,
+    check: [u8; CHECK_LEN],
+    id: String,
+    len: usize,
+}
+
+// version, cipher, key check, id length, id, nonce, ciphertext and tag
+fn header<T>(data: &[u8]) -> Result<Header, EncryptionError<T>> {
+    let malformed = || EncryptionError::Malformed;
+
+    if data.len() < 3 + CHECK_LEN || data[0] != VERSION {
+        return Err(malformed());
+    }
+
+    let cipher = Cipher::from_code(data[1]).ok_or_else(malformed)?;
+    let mut check = [0u8; CHECK_LEN];
+    check.copy_from_slice(&data[2..2 + CHECK_LEN]);
+    let id_len = data[2 + CHECK_LEN] as usize;
+    let len = 3 + CHECK_LEN + id_len;
+    let id = data.get(3 + CHECK_LEN..len).ok_or_else(malformed)?;
+    let id = String::from_utf8(id.to_vec()).map_err(|_| malformed())?;
+
+    if data.len() < len + NONCE_LEN + cipher.algorithm().tag_len() {
+        return Err(malformed());
+    }
+
+    Ok(Header {
+        cipher,
+        check,
+        id,
+        len,
+    })
+}
+
+fn seal<T>(key: &Key, mut data: Vec<u8>) -> Result<Vec<u8>, EncryptionError<T>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| EncryptionError::Crypto)?;
+
+    let mut envelope = vec![VERSION, key.cipher.code()];
+    envelope.extend_from_slice(&key.check());
+    envelope.push(key.id.len() as u8);
+    envelope.extend_from_slice(key.id.as_bytes());
+
+    key.aead()
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(&envelope),
+            &mut data,
+        )
+        .map_err(|_| EncryptionError::Crypto)?;
+
+    envelope.extend_from_slice(&nonce);
+    envelope.extend(data);
+
+    Ok(envelope)
+}
+
+fn open<T>(key: &Key, header: &Header, mut data: Vec<u8>) -> Result<Vec<u8>, EncryptionError<T>> {
+    if key.cipher != header.cipher || key.check() != header.check {
+        return Err(EncryptionError::WrongKey(header.id.clone()));
+    }
+
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce.copy_from_slice(&data[header.len..header.len + NONCE_LEN]);
+    let mut sealed = data.split_off(header.len + NONCE_LEN);
+    data.truncate(header.len);
+
+    let len = key
+        .aead()
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(&data),
+            &mut sealed,
+        )
+        .map_err(|_| EncryptionError::Tampered)?
+        .len();
+    sealed.truncate(len);
+
+    Ok(sealed)
+}
+
+/// Wraps the codec `U`, encrypting what it dumps with the current core's `Key`. The key is
+/// acquired when the returned future is first polled, so it must be polled within a core.
+pub struct Encrypted<U>(PhantomData<U>);
+
+impl<T: Send + 'static, U: Rehydrate<T> + 'static> Rehydrate<T> for Encrypted<U>
+where
+    U::Rehydrate: Send,
+    U::RehydrateError: Send,
+    U::Dump: Send,
+    U::DumpError: Send,
+{
+    type RehydrateError = EncryptionError<U::RehydrateError>;
+    type Rehydrate = Pin<Box<dyn Future<Output = Result<T, Self::RehydrateError>> + Send>>;
+    type DumpError = EncryptionError<U::DumpError>;
+    type Dump = Pin<Box<dyn Future<Output = Result<Vec<u8>, Self::DumpError>> + Send>>;
+
+    fn rehydrate(data: Vec<u8>) -> Self::Rehydrate {
+        let header = match header(&data) {
+            Ok(header) => header,
+            Err(e) => return Box::pin(ready(Err(e))),
+        };
+
+        Box::pin(async move {
+            let named = find(Some(header.id.clone()));
+
+            let key = match named.await.map_err(EncryptionError::Core)? {
+                Some(key) => key,
+                None => find(None)
+                    .await
+                    .map_err(EncryptionError::Core)?
+                    .filter(|key| key.id == header.id)
+                    .ok_or_else(|| EncryptionError::MissingKey(Some(header.id.clone())))?,
+            };
+
+            let data = open(&key, &header, data)?;
+
+            U::rehydrate(data).await.map_err(EncryptionError::Codec)
+        })
+    }
+    fn dump(data: T) -> Self::Dump {
+        let dump = U::dump(data);
+
+        Box::pin(async move {
+            let key = find(None)
+                .await
+                .map_err(EncryptionError::Core)?
+                .ok_or(EncryptionError::MissingKey(None))?;
+
+            seal(&key, dump.await.map_err(EncryptionError::Codec)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{register, register_named, register_with, Cbor, Core, CoreFutureExt, Lifetime};
+    use core::convert::Infallible;
+    use futures::executor::block_on;
+
+    fn key(id: &str, byte: u8) -> Key {
+        Key::new(id, Cipher::ChaCha20Poly1305, vec![byte; 32]).unwrap()
+    }
+
+    fn core(current: Option<Key>, retired: Vec<(&str, Key)>) -> Core {
+        let core = Core::new();
+
+        block_on(
+            async move {
+                if let Some(key) = current {
+                    register(move || ready(Ok::<_, Infallible>(key.clone()))).await?;
+                }
+
+                for (id, key) in retired {
+                    register_named(id, move || ready(Ok::<_, Infallible>(key.clone()))).await?;
+                }
+
+                Ok::<_, CoreError>(())
+            }
+            .in_core(&core),
+        )
+        .unwrap();
+
+        core
+    }
+
+    fn dump(core: &Core, item: &str) -> Result<Vec<u8>, EncryptionError<serde_cbor::Error>> {
+        block_on(Encrypted::<Cbor>::dump(item.to_owned()).in_core(core))
+    }
+
+    fn rehydrate(core: &Core, data: Vec<u8>) -> Result<String, EncryptionError<serde_cbor::Error>> {
+        block_on(Encrypted::<Cbor>::rehydrate(data).in_core(core))
+    }
+
+    #[test]
+    fn round_trips_within_a_core() {
+        let core = core(Some(key("current", 1)), vec![]);
+        let data = dump(&core, "secret").unwrap();
+
+        assert!(!data.windows(6).any(|window| window == b"secret"));
+        assert_eq!(rehydrate(&core, data).unwrap(), "secret");
+    }
+
+    #[test]
+    fn shared_keys_are_found() {
+        let core = Core::new();
+
+        block_on(
+            async {
+                register_with(Lifetime::Singleton, || {
+                    ready(Ok::<_, Infallible>(key("current", 1)))
+                })
+                .await
+            }
+            .in_core(&core),
+        )
+        .unwrap();
+
+        let data = dump(&core, "secret").unwrap();
+        assert_eq!(rehydrate(&core, data).unwrap(), "secret");
+    }
+
+    #[test]
+    fn different_keys_with_the_same_id_are_detected() {
+        let data = dump(&core(Some(key("current", 1)), vec![]), "secret").unwrap();
+
+        assert!(matches!(
+            rehydrate(&core(Some(key("current", 2)), vec![]), data),
+            Err(EncryptionError::WrongKey(id)) if id == "current"
+        ));
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let core = core(Some(key("k1", 1)), vec![("k2", key("k2", 1))]);
+        let data = dump(&core, "secret").unwrap();
+
+        let mut ciphertext = data.clone();
+        *ciphertext.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            rehydrate(&core, ciphertext),
+            Err(EncryptionError::Tampered)
+        ));
+
+        // the id is authenticated, so pointing the envelope at another copy of the key fails
+        let mut aad = data;
+        let id = 3 + CHECK_LEN;
+        assert_eq!(&aad[id..id + 2], b"k1");
+        aad[id + 1] = b'2';
+        assert!(matches!(
+            rehydrate(&core, aad),
+            Err(EncryptionError::Tampered)
+        ));
+    }
+
+    #[test]
+    fn missing_keys_are_reported() {
+        let empty = core(None, vec![]);
+
+        assert!(matches!(
+            dump(&empty, "secret"),
+            Err(EncryptionError::MissingKey(None))
+        ));
+
+        let data = dump(&core(Some(key("current", 1)), vec![]), "secret").unwrap();
+
+        assert!(matches!(
+            rehydrate(&empty, data),
+            Err(EncryptionError::MissingKey(Some(id))) if id == "current"
+        ));
+    }
+
+    #[test]
+    fn rotated_data_stays_readable() {
+        let old = dump(&core(Some(key("2019", 1)), vec![]), "old").unwrap();
+
+        let rotated = core(Some(key("2020", 2)), vec![("2019", key("2019", 1))]);
+        let new = dump(&rotated, "new").unwrap();
+
+        assert_eq!(rehydrate(&rotated, old).unwrap(), "old");
+        assert_eq!(rehydrate(&rotated, new.clone()).unwrap(), "new");
+        assert!(matches!(
+            rehydrate(&core(Some(key("2019", 1)), vec![]), new),
+            Err(EncryptionError::MissingKey(_))
+        ));
+    }
+}
@@ -1,23 +1,40 @@
 use core::{
-    any::{Any, TypeId},
+    any::{type_name, Any, TypeId},
     cell::RefCell,
     convert::{TryFrom, TryInto},
+    fmt::{self, Debug, Display, Formatter},
     future::Future,
+    marker::PhantomData,
     pin::Pin,
+    str::FromStr,
     task::{Context, Poll},
 };
 use core_error::Error;
 use futures::{
-    future::{ready, FutureObj, Ready},
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    future::{ready, FutureExt, FutureObj, LocalFutureObj, Map, Ready, RemoteHandle},
     lock::Mutex,
-    task::{Spawn, SpawnError},
-    TryFutureExt,
+    stream::{once, Concat, Once, StreamExt},
+    task::{LocalSpawn, LocalSpawnExt, Spawn, SpawnError},
+    Stream, TryFutureExt,
 };
+#[cfg(any(feature = "ring-sha256", feature = "ring-sha512"))]
+use ring::digest::Context as Digest;
 #[cfg(feature = "ring-sha256")]
-use ring::digest::{digest, SHA256};
-use serde::{de::DeserializeOwned, Serialize};
+use ring::digest::SHA256;
+#[cfg(feature = "ring-sha512")]
+use ring::digest::SHA512;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 use serde_cbor::{from_slice, to_vec, Error as CborError};
-use std::{collections::HashMap, hash::Hash, sync::Arc};
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    mem,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
 use thiserror::Error;
 
 #[doc(hidden)]
@@ -33,6 +50,11 @@ pub use containerized::{_vessel_entry_construct, _vessel_unravel};
 
 pub mod runtime;
 
+pub mod remote;
+
+mod builder;
+pub use builder::{CoreBuilder, Dependency};
+
 pub mod resource;
 #[doc(inline)]
 pub use resource::Resource;
@@ -43,9 +65,34 @@ pub use memory_store::MemoryStore;
 mod simple_resource_manager;
 pub use simple_resource_manager::SimpleResourceManager;
 
+mod sha256;
+pub use sha256::PortableSha256;
+
+#[cfg(any(feature = "json", feature = "bincode", feature = "messagepack"))]
+mod codecs;
+#[cfg(feature = "bincode")]
+pub use codecs::Bincode;
+#[cfg(feature = "json")]
+pub use codecs::Json;
+#[cfg(feature = "messagepack")]
+pub use codecs::MessagePack;
+
+pub mod compression;
+pub use compression::Compressed;
+
+#[cfg(feature = "ring-aead")]
+pub mod encryption;
+#[cfg(feature = "ring-aead")]
+pub use encryption::Encrypted;
+
+mod encoding;
+pub use encoding::ParseHashError;
+use encoding::{base32, decode, hex, DigestVisitor, FixedDigest};
+
 use resource::{
     hash::{Algorithm, Hasher},
-    Rehydrate,
+    multihash::HashCode,
+    Chunks, Rehydrate, StreamingRehydrate,
 };
 
 #[macro_export]
@@ -59,32 +106,149 @@ macro_rules! with_core {
     };};
 }
 
+/// `Hasher<Sha256>` backed by `ring`.
 #[cfg(feature = "ring-sha256")]
+#[derive(Clone)]
 pub struct Ring {
-    data: Vec<u8>,
+    context: Digest,
 }
 
 #[cfg(feature = "ring-sha256")]
 impl Hasher<Sha256> for Ring {
     fn new() -> Self {
-        Ring { data: vec![] }
+        Ring {
+            context: Digest::new(&SHA256),
+        }
     }
 
     fn write(&mut self, data: &[u8]) {
-        self.data.extend_from_slice(data)
+        self.context.update(data)
     }
 
     fn hash(&self) -> Sha256Sum {
-        let hash = digest(&SHA256, &self.data);
+        let hash = self.context.clone().finish();
         let mut sum = [0u8; 32];
         sum.copy_from_slice(hash.as_ref());
         Sha256Sum(sum)
     }
 }
 
+/// `Hasher<Sha512>` backed by `ring`.
+#[cfg(feature = "ring-sha512")]
+#[derive(Clone)]
+pub struct RingSha512 {
+    context: Digest,
+}
+
+#[cfg(feature = "ring-sha512")]
+impl Hasher<Sha512> for RingSha512 {
+    fn new() -> Self {
+        RingSha512 {
+            context: Digest::new(&SHA512),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        self.context.update(data)
+    }
+
+    fn hash(&self) -> Sha512Sum {
+        let hash = self.context.clone().finish();
+        let mut sum = [0u8; 64];
+        sum.copy_from_slice(hash.as_ref());
+        Sha512Sum(sum)
+    }
+}
+
+/// `Hasher<Blake3>` backed by the `blake3` crate.
+#[cfg(feature = "blake3")]
+#[derive(Clone)]
+pub struct Blake3Hasher {
+    hasher: blake3::Hasher,
+}
+
+#[cfg(feature = "blake3")]
+impl Hasher<Blake3> for Blake3Hasher {
+    fn new() -> Self {
+        Blake3Hasher {
+            hasher: blake3::Hasher::new(),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn hash(&self) -> Blake3Sum {
+        Blake3Sum(*self.hasher.finalize().as_bytes())
+    }
+}
+
+macro_rules! digest {
+    ($sum:ident, $len:expr) => {
+        impl FixedDigest for $sum {
+            const LEN: usize = $len;
+
+            fn from_slice(data: &[u8]) -> Self {
+                let mut sum = [0u8; $len];
+                sum.copy_from_slice(data);
+                $sum(sum)
+            }
+        }
+
+        impl Display for $sum {
+            fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+                if formatter.alternate() {
+                    formatter.write_str(&base32(&self.0))
+                } else {
+                    formatter.write_str(&hex(&self.0))
+                }
+            }
+        }
+
+        impl Debug for $sum {
+            fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+                write!(formatter, concat!(stringify!($sum), "({})"), self)
+            }
+        }
+
+        impl FromStr for $sum {
+            type Err = ParseHashError;
+
+            fn from_str(data: &str) -> Result<Self, ParseHashError> {
+                let mut sum = [0u8; $len];
+                decode(data, &mut sum)?;
+                Ok($sum(sum))
+            }
+        }
+
+        impl Serialize for $sum {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_str(self)
+                } else {
+                    serializer.serialize_bytes(&self.0)
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $sum {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                if deserializer.is_human_readable() {
+                    deserializer.deserialize_str(DigestVisitor(PhantomData))
+                } else {
+                    deserializer.deserialize_bytes(DigestVisitor(PhantomData))
+                }
+            }
+        }
+    };
+}
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Sha256Sum(pub [u8; 32]);
 
+digest!(Sha256Sum, 32);
+
 #[derive(Clone, Copy)]
 pub struct Sha256;
 
@@ -92,6 +256,69 @@ impl Algorithm for Sha256 {
     type Hash = Sha256Sum;
 }
 
+impl HashCode for Sha256 {
+    const CODE: u64 = 0x12;
+    const NAME: &'static str = "sha256";
+
+    fn to_digest(hash: &Sha256Sum) -> Vec<u8> {
+        hash.0.to_vec()
+    }
+
+    fn from_digest(digest: &[u8]) -> Option<Sha256Sum> {
+        digest.try_into().ok().map(Sha256Sum)
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Sha512Sum(pub [u8; 64]);
+
+digest!(Sha512Sum, 64);
+
+#[derive(Clone, Copy)]
+pub struct Sha512;
+
+impl Algorithm for Sha512 {
+    type Hash = Sha512Sum;
+}
+
+impl HashCode for Sha512 {
+    const CODE: u64 = 0x13;
+    const NAME: &'static str = "sha512";
+
+    fn to_digest(hash: &Sha512Sum) -> Vec<u8> {
+        hash.0.to_vec()
+    }
+
+    fn from_digest(digest: &[u8]) -> Option<Sha512Sum> {
+        digest.try_into().ok().map(Sha512Sum)
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Blake3Sum(pub [u8; 32]);
+
+digest!(Blake3Sum, 32);
+
+#[derive(Clone, Copy)]
+pub struct Blake3;
+
+impl Algorithm for Blake3 {
+    type Hash = Blake3Sum;
+}
+
+impl HashCode for Blake3 {
+    const CODE: u64 = 0x1e;
+    const NAME: &'static str = "blake3";
+
+    fn to_digest(hash: &Blake3Sum) -> Vec<u8> {
+        hash.0.to_vec()
+    }
+
+    fn from_digest(digest: &[u8]) -> Option<Blake3Sum> {
+        digest.try_into().ok().map(Blake3Sum)
+    }
+}
+
 pub struct Cbor;
 
 impl<T: DeserializeOwned + Serialize> Rehydrate<T> for Cbor {
@@ -108,6 +335,18 @@ impl<T: DeserializeOwned + Serialize> Rehydrate<T> for Cbor {
     }
 }
 
+impl<T: DeserializeOwned + Serialize> StreamingRehydrate<T> for Cbor {
+    type RehydrateStream = Map<Concat<Chunks>, fn(Vec<u8>) -> Result<T, CborError>>;
+    type DumpStream = Once<Ready<Result<Vec<u8>, CborError>>>;
+
+    fn rehydrate_stream(data: Chunks) -> Self::RehydrateStream {
+        data.concat().map(|data| from_slice(&data))
+    }
+    fn dump_stream(data: T) -> Self::DumpStream {
+        once(ready(to_vec(&data)))
+    }
+}
+
 pub struct Convert;
 
 impl<T: TryFrom<Vec<u8>> + TryInto<Vec<u8>>> Rehydrate<T> for Convert {
@@ -124,203 +363,1980 @@ impl<T: TryFrom<Vec<u8>> + TryInto<Vec<u8>>> Rehydrate<T> for Convert {
     }
 }
 
+impl<T: TryFrom<Vec<u8>> + TryInto<Vec<u8>>> StreamingRehydrate<T> for Convert {
+    type RehydrateStream = Map<Concat<Chunks>, fn(Vec<u8>) -> Result<T, Self::RehydrateError>>;
+    type DumpStream = Once<Ready<Result<Vec<u8>, Self::DumpError>>>;
+
+    fn rehydrate_stream(data: Chunks) -> Self::RehydrateStream {
+        data.concat().map(T::try_from)
+    }
+    fn dump_stream(data: T) -> Self::DumpStream {
+        once(ready(data.try_into()))
+    }
+}
+
 pub struct Core {
     singleton: Singleton,
 }
 
 impl Core {
     pub fn new() -> Self {
+        let torn_down = Arc::new(AtomicBool::new(false));
+
         Core {
             singleton: Singleton {
                 local: Arc::new(Mutex::new(HashMap::new())),
+                scope: Arc::new(Mutex::new(HashMap::new())),
+                providers: Arc::new(Mutex::new(vec![])),
+                hooks: Arc::new(Mutex::new(vec![])),
+                torn_down: torn_down.clone(),
+                root_torn_down: torn_down,
+                trace: Arc::new(Mutex::new(None)),
+                watchers: Arc::new(Mutex::new(HashMap::new())),
+                parent: None,
             },
         }
     }
-}
 
-pub trait Provider {
-    fn acquire(
-        &self,
-        ty: TypeId,
-    ) -> Pin<
-        Box<dyn Future<Output = Result<Option<Box<dyn Any + Send>>, Box<dyn Error + Send>>> + Send>,
-    >;
+    /// Creates a core that resolves registrations it does not contain itself against `parent`
+    /// and its ancestors. Registering in the child never affects the parent.
+    pub fn child(parent: &Core) -> Self {
+        let torn_down = Arc::new(AtomicBool::new(false));
 
-    fn box_clone(&self) -> Box<dyn Provider + Send>;
-}
+        Core {
+            singleton: Singleton {
+                local: Arc::new(Mutex::new(HashMap::new())),
+                scope: Arc::new(Mutex::new(HashMap::new())),
+                providers: Arc::new(Mutex::new(vec![])),
+                hooks: Arc::new(Mutex::new(vec![])),
+                torn_down: torn_down.clone(),
+                root_torn_down: torn_down,
+                trace: Arc::new(Mutex::new(None)),
+                watchers: Arc::new(Mutex::new(HashMap::new())),
+                parent: Some(Box::new(parent.singleton.private_clone())),
+            },
+        }
+    }
 
-pub struct Singleton {
-    local: Arc<
-        Mutex<
-            HashMap<
-                TypeId,
-                Box<
-                    dyn Fn() -> Pin<
-                            Box<
-                                dyn Future<
-                                        Output = Result<Box<dyn Any + Send>, Box<dyn Error + Send>>,
-                                    > + Send,
-                            >,
-                        > + Send,
-                >,
-            >,
-        >,
-    >,
-}
+    /// Creates a new scope sharing this core's registrations, in which `Lifetime::Scoped`
+    /// registrations are instantiated afresh. A scope has its own disposal hooks, and shutting
+    /// it down leaves the core it was created from intact.
+    pub fn scope(&self) -> Self {
+        Core {
+            singleton: Singleton {
+                local: self.singleton.local.clone(),
+                scope: Arc::new(Mutex::new(HashMap::new())),
+                providers: self.singleton.providers.clone(),
+                hooks: Arc::new(Mutex::new(vec![])),
+                torn_down: Arc::new(AtomicBool::new(false)),
+                root_torn_down: self.singleton.root_torn_down.clone(),
+                trace: self.singleton.trace.clone(),
+                watchers: self.singleton.watchers.clone(),
+                parent: self
+                    .singleton
+                    .parent
+                    .as_deref()
+                    .map(Singleton::private_clone)
+                    .map(Box::new),
+            },
+        }
+    }
 
-impl Singleton {
-    fn private_clone(&self) -> Self {
-        Singleton {
-            local: self.local.clone(),
+    /// Appends a provider consulted, in insertion order, for types and keys missing from this
+    /// core's own registrations before resolution falls back to the parent core.
+    pub fn add_provider(&self, provider: Box<dyn Provider + Send>) -> impl Future<Output = ()> {
+        let providers = self.singleton.providers.clone();
+
+        async move {
+            providers.lock().await.push(provider);
         }
     }
 
-    fn acquire<T: Any>(&self) -> impl Future<Output = Result<Option<T>, Box<dyn Error + Send>>> {
-        let this = self.local.clone();
+    /// Lists the registrations visible from this core, including those of its ancestors.
+    pub fn describe(&self) -> impl Future<Output = Vec<Description>> {
+        let mut next = Some(self.singleton.private_clone());
 
         async move {
-            let this = this.lock().await;
+            let mut descriptions = vec![];
+            let mut depth = 0;
 
-            if let Some(call) = this.get(&TypeId::of::<T>()) {
-                Ok(Some(
-                    *Box::<dyn Any + Send>::downcast::<T>((call)().await?).unwrap(),
-                ))
-            } else {
-                Ok(None)
+            while let Some(singleton) = next {
+                let registrations = singleton
+                    .local
+                    .lock()
+                    .await
+                    .iter()
+                    .map(|(key, registration)| (key.clone(), registration.clone()))
+                    .collect::<Vec<_>>();
+
+                for ((id, key), registration) in registrations {
+                    descriptions.push(Description {
+                        id,
+                        ty: registration.ty,
+                        key,
+                        lifetime: registration.instance.lifetime(),
+                        created: registration.created.load(Ordering::SeqCst),
+                        last_error: registration.last_error.lock().await.clone(),
+                        depth,
+                    });
+                }
+
+                depth += 1;
+                next = singleton.parent.as_deref().map(Singleton::private_clone);
             }
+
+            descriptions.sort_by(|a, b| (a.depth, a.ty, &a.key).cmp(&(b.depth, b.ty, &b.key)));
+            descriptions
         }
     }
 
-    fn register<
-        T: Any + Send,
-        F: Fn() -> Fut + Send + 'static,
-        Fut: Future<Output = Result<T, E>> + Send + 'static,
-        E: Error + Send + 'static,
-    >(
+    /// Installs a hook invoked on every registration and acquisition performed through this
+    /// core, replacing any previous one.
+    pub fn set_trace<F: Fn(&Event) + Send + Sync + 'static>(
         &self,
-        cb: F,
-    ) -> impl Future<Output = Result<(), Box<dyn Error + Send>>> {
-        let this = self.local.clone();
+        trace: F,
+    ) -> impl Future<Output = ()> {
+        let cell = self.singleton.trace.clone();
 
         async move {
-            let mut this = this.lock().await;
+            *cell.lock().await = Some(Arc::new(trace));
+        }
+    }
 
-            this.insert(
-                TypeId::of::<T>(),
-                Box::new(move || {
-                    Box::pin(
-                        (cb)()
-                            .map_ok(|item| Box::new(item) as Box<dyn Any + Send>)
-                            .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
-                    )
-                }),
-            );
+    /// Tears down the core, running its disposal hooks from the most to the least recently
+    /// registered and releasing every instance it holds. Acquisitions, registrations and hooks
+    /// through this core fail with `CoreError::TornDown` from then on, and watchers see every
+    /// registration as unregistered. Shutting down a scope only releases its own hooks and scoped
+    /// instances.
+    pub fn shutdown(&self) -> impl Future<Output = Result<(), ShutdownError>> {
+        let singleton = self.singleton.private_clone();
 
-            Ok(())
+        async move {
+            // set under the hooks lock so that no hook can be added after they are taken
+            let hooks = {
+                let mut hooks = singleton.hooks.lock().await;
+                singleton.torn_down.store(true, Ordering::SeqCst);
+                mem::replace(&mut *hooks, vec![])
+            };
+            let mut errors = vec![];
+
+            for hook in hooks.into_iter().rev() {
+                if let Err(e) = (hook)().await {
+                    errors.push(e);
+                }
+            }
+
+            if !singleton.is_scope() {
+                let removed = mem::replace(&mut *singleton.local.lock().await, HashMap::new());
+                let mut watchers = singleton.watchers.lock().await;
+
+                for key in removed.keys() {
+                    notify(&mut *watchers, key, Change::Unregistered);
+                }
+            }
+            singleton.scope.lock().await.clear();
+
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(ShutdownError(errors))
+            }
         }
     }
 }
 
-thread_local! {
-    static CURRENT_SINGLETON: RefCell<Option<Singleton>> = RefCell::new(None);
+#[derive(Debug, Clone)]
+pub struct Description {
+    pub id: TypeId,
+    pub ty: &'static str,
+    pub key: Option<String>,
+    pub lifetime: Lifetime,
+    pub created: usize,
+    pub last_error: Option<String>,
+    /// Number of parent links between the described core and the one holding the registration.
+    pub depth: usize,
 }
 
-#[derive(Debug, Error)]
-pub enum CoreError {
-    #[error("error from core: {0}")]
-    Error(
-        #[source]
-        #[from]
-        Box<dyn Error + Send>,
-    ),
-    #[error("no active core")]
-    NoCore,
+#[derive(Debug)]
+pub enum Event<'a> {
+    Register {
+        ty: &'static str,
+        key: Option<&'a str>,
+    },
+    Unregister {
+        ty: &'static str,
+        key: Option<&'a str>,
+    },
+    /// `result` is whether a registration or provider was found, or the error encountered.
+    Acquire {
+        ty: &'static str,
+        key: Option<&'a str>,
+        result: Result<bool, &'a CoreError>,
+    },
 }
 
-fn get_singleton() -> Option<Singleton> {
-    let mut ptr: Option<Singleton> = None;
+#[derive(Debug, Error)]
+#[error("{} disposal hook(s) failed during shutdown", .0.len())]
+pub struct ShutdownError(pub Vec<Box<dyn Error + Send>>);
 
-    CURRENT_SINGLETON.with(|singleton| {
-        singleton.borrow().as_ref().map(|singleton| {
-            ptr = Some(singleton.private_clone());
-        });
-    });
+/// Supplies items a core has no registration for. `key` is the name passed to `acquire_named`,
+/// or `None` for `acquire`.
+pub trait Provider {
+    fn acquire(
+        &self,
+        ty: TypeId,
+        key: Option<String>,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<Option<Box<dyn Any + Send>>, Box<dyn Error + Send>>> + Send>,
+    >;
 
-    ptr
+    fn box_clone(&self) -> Box<dyn Provider + Send>;
 }
 
-pub fn acquire<T: Any>() -> impl Future<Output = Result<Option<T>, CoreError>> {
-    let singleton = get_singleton().ok_or(CoreError::NoCore);
-
-    async { Ok(singleton?.acquire::<T>().await?) }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lifetime {
+    Singleton,
+    Transient,
+    Scoped,
 }
 
-pub fn register<
-    T: Any + Send,
-    F: Fn() -> Fut + Send + 'static,
-    Fut: Future<Output = Result<T, E>> + Send + 'static,
-    E: Error + Send + 'static,
->(
-    cb: F,
-) -> impl Future<Output = Result<(), CoreError>> {
-    let singleton = get_singleton().ok_or(CoreError::NoCore);
+type Factory = Box<
+    dyn Fn() -> Pin<
+            Box<dyn Future<Output = Result<Box<dyn Any + Send>, Box<dyn Error + Send>>> + Send>,
+        > + Send,
+>;
+
+type Share = fn(&(dyn Any + Send)) -> Box<dyn Any + Send>;
 
-    async move { Ok(singleton?.register(cb).await?) }
+fn share<T: ?Sized + Any + Send + Sync>(instance: &(dyn Any + Send)) -> Box<dyn Any + Send> {
+    Box::new(instance.downcast_ref::<Arc<T>>().unwrap().clone())
 }
 
-fn use_singleton(new_singleton: Option<Singleton>) {
-    CURRENT_SINGLETON.with(|singleton| {
-        *singleton.borrow_mut() = new_singleton;
-    });
+type Cell = Arc<Mutex<Option<Box<dyn Any + Send>>>>;
+
+enum Instance {
+    Transient,
+    Singleton(Share, Cell),
+    Scoped(Share),
 }
 
-pub struct CoreGuard(Option<Singleton>);
+impl Instance {
+    fn lifetime(&self) -> Lifetime {
+        match self {
+            Instance::Transient => Lifetime::Transient,
+            Instance::Singleton(..) => Lifetime::Singleton,
+            Instance::Scoped(_) => Lifetime::Scoped,
+        }
+    }
 
-impl Drop for CoreGuard {
-    fn drop(&mut self) {
-        use_singleton(self.0.take())
+    fn shared<T: ?Sized + Any + Send + Sync>(lifetime: Lifetime) -> Self {
+        match lifetime {
+            Lifetime::Singleton => Instance::Singleton(share::<T>, Arc::new(Mutex::new(None))),
+            Lifetime::Transient => Instance::Transient,
+            Lifetime::Scoped => Instance::Scoped(share::<T>),
+        }
     }
 }
 
-#[doc(hidden)]
-pub unsafe fn _inner_use_core(core: Option<&Core>) -> CoreGuard {
-    let guard = get_singleton()
-        .map(|singleton| CoreGuard(Some(singleton)))
-        .unwrap_or(CoreGuard(None));
+static REGISTRATIONS: AtomicUsize = AtomicUsize::new(0);
 
-    use_singleton(core.map(|core| core.singleton.private_clone()));
+struct Registration {
+    id: usize,
+    ty: &'static str,
+    factory: Mutex<Factory>,
+    instance: Instance,
+    created: AtomicUsize,
+    last_error: Mutex<Option<String>>,
+}
 
-    guard
+impl Registration {
+    // unlike the address of a registration, never reused once it has been replaced
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    async fn create(&self, core: Core) -> Result<Box<dyn Any + Send>, CoreError> {
+        // the factory lock only guards construction of the future, never its execution
+        let future = (self.factory.lock().await)();
+
+        match InCore::new(Resolving::new(future, self.id(), self.ty), Some(core)).await {
+            Ok(item) => {
+                self.created.fetch_add(1, Ordering::SeqCst);
+                Ok(item)
+            }
+            Err(source) => {
+                *self.last_error.lock().await = Some(source.to_string());
+
+                // a cycle is reported as such however many factories it propagated through
+                match source.downcast::<CoreError>() {
+                    Ok(error) => match *error {
+                        CoreError::Cycle(cycle) => Err(CoreError::Cycle(cycle)),
+                        error => Err(CoreError::Factory {
+                            ty: self.ty,
+                            source: Box::new(error),
+                        }),
+                    },
+                    Err(source) => Err(CoreError::Factory {
+                        ty: self.ty,
+                        source,
+                    }),
+                }
+            }
+        }
+    }
 }
 
-pub struct CorePreserver<T: Spawn>(pub T);
+thread_local! {
+    static RESOLVING: RefCell<Vec<(usize, &'static str)>> = RefCell::new(vec![]);
+}
 
-struct CoreTask<F: Future> {
+// marks a registration as under construction for as long as its factory is being polled, which
+// is when any acquisition the factory performs is initiated
+struct Resolving<F: Future> {
     future: F,
-    core: Option<Core>,
+    id: usize,
+    ty: &'static str,
 }
 
-impl<F: Future> CoreTask<F> {
-    fn new(future: F, core: Option<Core>) -> Self {
-        CoreTask { future, core }
+impl<F: Future> Resolving<F> {
+    fn new(future: F, id: usize, ty: &'static str) -> Self {
+        Resolving { future, id, ty }
     }
 }
 
-impl<F: Future + Unpin> Future for CoreTask<F> {
+impl<F: Future + Unpin> Future for Resolving<F> {
     type Output = F::Output;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        let guard = unsafe { _inner_use_core(self.core.as_ref()) };
-        let output = Pin::new(&mut self.future).poll(cx);
-        drop(guard);
-        output
+        let entry = (self.id, self.ty);
+        RESOLVING.with(|resolving| resolving.borrow_mut().push(entry));
+        let _resolved = Resolved;
+        Pin::new(&mut self.future).poll(cx)
     }
 }
 
-impl<T: Spawn> Spawn for CorePreserver<T> {
-    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
-        let core = get_singleton().map(|singleton| Core { singleton });
-        self.0
-            .spawn_obj(Box::pin(CoreTask::new(future, core)).into())
+// pops the entry pushed by `Resolving::poll` even if the factory panics, so that a thread which
+// survives the panic doesn't report every later acquisition of the registration as a cycle
+struct Resolved;
+
+impl Drop for Resolved {
+    fn drop(&mut self) {
+        RESOLVING.with(|resolving| resolving.borrow_mut().pop());
+    }
+}
+
+fn downcast<T: Any>(item: Box<dyn Any + Send>) -> Result<T, CoreError> {
+    Box::<dyn Any + Send>::downcast::<T>(item)
+        .map(|item| *item)
+        .map_err(|_| CoreError::TypeMismatch(type_name::<T>()))
+}
+
+type Key = (TypeId, Option<String>);
+
+type Trace = Arc<dyn Fn(&Event) + Send + Sync>;
+
+/// Stream of the changes made to a single registration, see `watch`.
+pub type Watch = UnboundedReceiver<Change>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Registered,
+    Replaced,
+    Unregistered,
+}
+
+fn notify(watchers: &mut HashMap<Key, Vec<UnboundedSender<Change>>>, key: &Key, change: Change) {
+    if let Some(senders) = watchers.get_mut(key) {
+        senders.retain(|sender| sender.unbounded_send(change).is_ok());
+    }
+}
+
+type Hook = Box<
+    dyn FnOnce() -> Pin<Box<dyn Future<Output = Result<(), Box<dyn Error + Send>>> + Send>> + Send,
+>;
+
+pub struct Singleton {
+    local: Arc<Mutex<HashMap<Key, Arc<Registration>>>>,
+    // scoped instances, tagged with the id of the registration that created them
+    scope: Arc<Mutex<HashMap<Key, (usize, Cell)>>>,
+    providers: Arc<Mutex<Vec<Box<dyn Provider + Send>>>>,
+    hooks: Arc<Mutex<Vec<Hook>>>,
+    torn_down: Arc<AtomicBool>,
+    // the flag of the core this one is a scope of, or its own flag if it isn't a scope
+    root_torn_down: Arc<AtomicBool>,
+    trace: Arc<Mutex<Option<Trace>>>,
+    watchers: Arc<Mutex<HashMap<Key, Vec<UnboundedSender<Change>>>>>,
+    parent: Option<Box<Singleton>>,
+}
+
+impl Singleton {
+    fn private_clone(&self) -> Self {
+        Singleton {
+            local: self.local.clone(),
+            scope: self.scope.clone(),
+            providers: self.providers.clone(),
+            hooks: self.hooks.clone(),
+            torn_down: self.torn_down.clone(),
+            root_torn_down: self.root_torn_down.clone(),
+            trace: self.trace.clone(),
+            watchers: self.watchers.clone(),
+            parent: self
+                .parent
+                .as_deref()
+                .map(Singleton::private_clone)
+                .map(Box::new),
+        }
+    }
+
+    fn is_scope(&self) -> bool {
+        !Arc::ptr_eq(&self.torn_down, &self.root_torn_down)
+    }
+
+    fn is_torn_down(&self) -> bool {
+        self.torn_down.load(Ordering::SeqCst) || self.root_torn_down.load(Ordering::SeqCst)
+    }
+
+    fn acquire<T: Any>(
+        &self,
+        name: Option<String>,
+    ) -> impl Future<Output = Result<Option<T>, CoreError>> {
+        let trace = self.trace.clone();
+        let key = name.clone();
+        let resolve = self.resolve::<T>(name);
+
+        async move {
+            let result = resolve.await;
+
+            if let Some(trace) = trace.lock().await.clone() {
+                (trace)(&Event::Acquire {
+                    ty: type_name::<T>(),
+                    key: key.as_deref(),
+                    result: result.as_ref().map(Option::is_some),
+                });
+            }
+
+            result
+        }
+    }
+
+    fn resolve<T: Any>(
+        &self,
+        name: Option<String>,
+    ) -> impl Future<Output = Result<Option<T>, CoreError>> {
+        let ty = type_name::<T>();
+        let key = (TypeId::of::<T>(), name);
+        let resolving = RESOLVING.with(|resolving| resolving.borrow().clone());
+        let mut next = Some(self.private_clone());
+        let scope = self.scope.clone();
+        let core = Core {
+            singleton: self.private_clone(),
+        };
+
+        async move {
+            while let Some(singleton) = next {
+                if singleton.is_torn_down() {
+                    return Err(CoreError::TornDown(ty));
+                }
+
+                let registration = singleton.local.lock().await.get(&key).cloned();
+
+                let registration = if let Some(registration) = registration {
+                    registration
+                } else {
+                    // providers are cloned out so that none of them runs under the lock
+                    let providers = singleton
+                        .providers
+                        .lock()
+                        .await
+                        .iter()
+                        .map(|provider| provider.box_clone())
+                        .collect::<Vec<_>>();
+
+                    for provider in providers {
+                        let item = provider
+                            .acquire(key.0, key.1.clone())
+                            .await
+                            .map_err(|source| CoreError::Provider { ty, source })?;
+
+                        if let Some(item) = item {
+                            return Ok(Some(downcast(item)?));
+                        }
+                    }
+
+                    next = singleton.parent.as_deref().map(Singleton::private_clone);
+                    continue;
+                };
+
+                if let Some(position) = resolving
+                    .iter()
+                    .position(|(id, _)| *id == registration.id())
+                {
+                    let mut cycle = resolving[position..]
+                        .iter()
+                        .map(|(_, ty)| *ty)
+                        .collect::<Vec<_>>();
+                    cycle.push(ty);
+                    return Err(CoreError::Cycle(cycle));
+                }
+
+                let (share, cell) = match &registration.instance {
+                    Instance::Transient => {
+                        return Ok(Some(downcast(registration.create(core).await?)?));
+                    }
+                    Instance::Singleton(share, cell) => (share, cell.clone()),
+                    Instance::Scoped(share) => {
+                        let mut scope = scope.lock().await;
+                        let (id, cell) = scope
+                            .entry(key)
+                            .or_insert_with(|| (registration.id(), Arc::new(Mutex::new(None))));
+
+                        // the registration was replaced through another scope of the same core
+                        if *id != registration.id() {
+                            *id = registration.id();
+                            *cell = Arc::new(Mutex::new(None));
+                        }
+
+                        (share, cell.clone())
+                    }
+                };
+
+                // only acquisitions of this particular instance wait on its construction
+                let mut instance = cell.lock().await;
+
+                if instance.is_none() {
+                    *instance = Some(registration.create(core).await?);
+                }
+
+                return Ok(Some(downcast((share)(instance.as_deref().unwrap()))?));
+            }
+
+            Ok(None)
+        }
+    }
+
+    fn keys<T: Any>(&self) -> impl Future<Output = Vec<String>> {
+        let mut next = Some(self.private_clone());
+
+        async move {
+            let mut keys = vec![];
+
+            while let Some(singleton) = next {
+                for (ty, name) in singleton.local.lock().await.keys() {
+                    if let Some(name) = name {
+                        if *ty == TypeId::of::<T>() && !keys.contains(name) {
+                            keys.push(name.clone());
+                        }
+                    }
+                }
+
+                next = singleton.parent.as_deref().map(Singleton::private_clone);
+            }
+
+            keys.sort();
+            keys
+        }
+    }
+
+    fn insert(
+        &self,
+        key: Key,
+        ty: &'static str,
+        factory: Factory,
+        instance: Instance,
+        existing: bool,
+    ) -> impl Future<Output = Result<(), CoreError>> {
+        let singleton = self.private_clone();
+
+        async move {
+            let mut this = singleton.local.lock().await;
+
+            if singleton.is_torn_down() {
+                return Err(CoreError::TornDown(ty));
+            }
+
+            let change = if this.contains_key(&key) {
+                Change::Replaced
+            } else if existing {
+                return Err(CoreError::NotRegistered(ty));
+            } else {
+                Change::Registered
+            };
+
+            if let Some(trace) = singleton.trace.lock().await.clone() {
+                (trace)(&Event::Register {
+                    ty,
+                    key: key.1.as_deref(),
+                });
+            }
+
+            singleton.scope.lock().await.remove(&key);
+            notify(&mut *singleton.watchers.lock().await, &key, change);
+            this.insert(
+                key,
+                Arc::new(Registration {
+                    id: REGISTRATIONS.fetch_add(1, Ordering::SeqCst),
+                    ty,
+                    factory: Mutex::new(factory),
+                    instance,
+                    created: AtomicUsize::new(0),
+                    last_error: Mutex::new(None),
+                }),
+            );
+
+            Ok(())
+        }
+    }
+
+    fn unregister<T: Any>(&self, name: Option<String>) -> impl Future<Output = bool> {
+        let this = self.local.clone();
+        let scope = self.scope.clone();
+        let trace = self.trace.clone();
+        let watchers = self.watchers.clone();
+        let key = (TypeId::of::<T>(), name);
+
+        async move {
+            let mut this = this.lock().await;
+
+            scope.lock().await.remove(&key);
+
+            if this.remove(&key).is_none() {
+                return false;
+            }
+
+            if let Some(trace) = trace.lock().await.clone() {
+                (trace)(&Event::Unregister {
+                    ty: type_name::<T>(),
+                    key: key.1.as_deref(),
+                });
+            }
+
+            notify(&mut *watchers.lock().await, &key, Change::Unregistered);
+            true
+        }
+    }
+
+    fn watch<T: Any>(&self, name: Option<String>) -> impl Future<Output = Watch> {
+        let watchers = self.watchers.clone();
+        let key = (TypeId::of::<T>(), name);
+
+        async move {
+            let (sender, receiver) = unbounded();
+
+            watchers
+                .lock()
+                .await
+                .entry(key)
+                .or_insert_with(Vec::new)
+                .push(sender);
+
+            receiver
+        }
+    }
+
+    fn register<
+        T: Any + Send,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    >(
+        &self,
+        name: Option<String>,
+        existing: bool,
+        cb: F,
+    ) -> impl Future<Output = Result<(), CoreError>> {
+        self.insert(
+            (TypeId::of::<T>(), name),
+            type_name::<T>(),
+            Box::new(move || {
+                Box::pin(
+                    (cb)()
+                        .map_ok(|item| Box::new(item) as Box<dyn Any + Send>)
+                        .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+                )
+            }),
+            Instance::Transient,
+            existing,
+        )
+    }
+
+    fn register_with<
+        T: Any + Send + Sync,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    >(
+        &self,
+        name: Option<String>,
+        existing: bool,
+        lifetime: Lifetime,
+        cb: F,
+    ) -> impl Future<Output = Result<(), CoreError>> {
+        self.insert(
+            (TypeId::of::<Arc<T>>(), name),
+            type_name::<Arc<T>>(),
+            Box::new(move || {
+                Box::pin(
+                    (cb)()
+                        .map_ok(|item| Box::new(Arc::new(item)) as Box<dyn Any + Send>)
+                        .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+                )
+            }),
+            Instance::shared::<T>(lifetime),
+            existing,
+        )
+    }
+
+    fn register_as<
+        I: ?Sized + Any + Send + Sync,
+        F: Fn() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Box<I>, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    >(
+        &self,
+        name: Option<String>,
+        existing: bool,
+        lifetime: Lifetime,
+        cb: F,
+    ) -> impl Future<Output = Result<(), CoreError>> {
+        self.insert(
+            (TypeId::of::<Arc<I>>(), name),
+            type_name::<Arc<I>>(),
+            Box::new(move || {
+                Box::pin(
+                    (cb)()
+                        .map_ok(|item| Box::new(Arc::<I>::from(item)) as Box<dyn Any + Send>)
+                        .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+                )
+            }),
+            Instance::shared::<I>(lifetime),
+            existing,
+        )
+    }
+}
+
+thread_local! {
+    static CURRENT_SINGLETON: RefCell<Option<Singleton>> = RefCell::new(None);
+}
+
+#[derive(Debug, Error)]
+pub enum CoreError {
+    #[error("no registration for {0}")]
+    NotRegistered(&'static str),
+    #[error("factory for {ty} failed: {source}")]
+    Factory {
+        ty: &'static str,
+        #[source]
+        source: Box<dyn Error + Send>,
+    },
+    #[error("provider failed to supply {ty}: {source}")]
+    Provider {
+        ty: &'static str,
+        #[source]
+        source: Box<dyn Error + Send>,
+    },
+    #[error("registration for {0} produced a value of a different type")]
+    TypeMismatch(&'static str),
+    #[error("dependency cycle detected: {}", .0.join(" -> "))]
+    Cycle(Vec<&'static str>),
+    #[error("{0} used with a core that has been torn down")]
+    TornDown(&'static str),
+    #[error("no active core")]
+    NoCore,
+}
+
+fn get_singleton() -> Option<Singleton> {
+    let mut ptr: Option<Singleton> = None;
+
+    CURRENT_SINGLETON.with(|singleton| {
+        singleton.borrow().as_ref().map(|singleton| {
+            ptr = Some(singleton.private_clone());
+        });
+    });
+
+    ptr
+}
+
+pub fn acquire<T: Any>() -> impl Future<Output = Result<Option<T>, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async { singleton?.acquire::<T>(None).await }
+}
+
+/// Like `acquire`, but treats a missing registration as `CoreError::NotRegistered`.
+pub fn require<T: Any>() -> impl Future<Output = Result<T, CoreError>> {
+    let acquire = acquire::<T>();
+
+    async {
+        acquire
+            .await?
+            .ok_or(CoreError::NotRegistered(type_name::<T>()))
+    }
+}
+
+pub fn acquire_named<T: Any, K: Into<String>>(
+    key: K,
+) -> impl Future<Output = Result<Option<T>, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { singleton?.acquire::<T>(Some(key)).await }
+}
+
+/// Lists the keys of every named registration for `T` visible from the current core.
+pub fn keys<T: Any>() -> impl Future<Output = Result<Vec<String>, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async { Ok(singleton?.keys::<T>().await) }
+}
+
+pub fn register<
+    T: Any + Send,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register(None, false, cb).await }
+}
+
+pub fn register_named<
+    T: Any + Send,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { singleton?.register(Some(key), false, cb).await }
+}
+
+/// Registers a factory for `T` with an explicit `Lifetime`. Instances are shared as `Arc<T>`
+/// and acquired with `acquire::<Arc<T>>()`, even for `Lifetime::Transient`.
+pub fn register_with<
+    T: Any + Send + Sync,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register_with(None, false, lifetime, cb).await }
+}
+
+pub fn register_named_with<
+    T: Any + Send + Sync,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move {
+        singleton?
+            .register_with(Some(key), false, lifetime, cb)
+            .await
+    }
+}
+
+/// Like `register`, but fails with `CoreError::NotRegistered` unless the current core already
+/// holds a registration for `T` to replace.
+pub fn replace<
+    T: Any + Send,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register(None, true, cb).await }
+}
+
+pub fn replace_with<
+    T: Any + Send + Sync,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register_with(None, true, lifetime, cb).await }
+}
+
+pub fn replace_named<
+    T: Any + Send,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { singleton?.register(Some(key), true, cb).await }
+}
+
+pub fn replace_named_with<
+    T: Any + Send + Sync,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<T, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move {
+        singleton?
+            .register_with(Some(key), true, lifetime, cb)
+            .await
+    }
+}
+
+/// Removes the registration for `T` from the current core, returning whether there was one.
+/// Instances that were already acquired are unaffected.
+pub fn unregister<T: Any>() -> impl Future<Output = Result<bool, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async { Ok(singleton?.unregister::<T>(None).await) }
+}
+
+pub fn unregister_named<T: Any, K: Into<String>>(
+    key: K,
+) -> impl Future<Output = Result<bool, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { Ok(singleton?.unregister::<T>(Some(key)).await) }
+}
+
+/// Yields a `Change` whenever the registration for `T` in the current core is added, replaced
+/// or removed, including by `Core::shutdown`. Changes made to parent cores are not reported.
+/// `T` is the type as acquired, so registrations made with `register_with::<T>` or
+/// `register_as::<T>` are watched with `watch::<Arc<T>>()`.
+pub fn watch<T: Any>() -> impl Future<Output = Result<Watch, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async { Ok(singleton?.watch::<T>(None).await) }
+}
+
+pub fn watch_named<T: Any, K: Into<String>>(
+    key: K,
+) -> impl Future<Output = Result<Watch, CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { Ok(singleton?.watch::<T>(Some(key)).await) }
+}
+
+/// Registers a factory for an implementation of the interface `I`, usually a trait object.
+/// Instances are acquired as `Arc<I>`, e.g. `acquire::<Arc<dyn Storage>>()`, whatever the
+/// `Lifetime`. For unshared `Box<dyn Storage>` instances, `register` a factory returning them.
+pub fn register_as<
+    I: ?Sized + Any + Send + Sync,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Box<I>, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register_as(None, false, lifetime, cb).await }
+}
+
+pub fn register_named_as<
+    I: ?Sized + Any + Send + Sync,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Box<I>, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { singleton?.register_as(Some(key), false, lifetime, cb).await }
+}
+
+/// Like `register_as`, but fails with `CoreError::NotRegistered` unless the current core already
+/// holds a registration for `Arc<I>` to replace.
+pub fn replace_as<
+    I: ?Sized + Any + Send + Sync,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Box<I>, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move { singleton?.register_as(None, true, lifetime, cb).await }
+}
+
+pub fn replace_named_as<
+    I: ?Sized + Any + Send + Sync,
+    K: Into<String>,
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Box<I>, E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    key: K,
+    lifetime: Lifetime,
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+    let key = key.into();
+
+    async move { singleton?.register_as(Some(key), true, lifetime, cb).await }
+}
+
+/// Registers a disposal hook with the current core, to be run by `Core::shutdown`.
+pub fn on_shutdown<
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Error + Send + 'static,
+>(
+    cb: F,
+) -> impl Future<Output = Result<(), CoreError>> {
+    let singleton = get_singleton().ok_or(CoreError::NoCore);
+
+    async move {
+        let singleton = singleton?;
+        let mut hooks = singleton.hooks.lock().await;
+
+        if singleton.is_torn_down() {
+            return Err(CoreError::TornDown("disposal hook"));
+        }
+
+        hooks.push(Box::new(move || {
+            Box::pin((cb)().map_err(|e| Box::new(e) as Box<dyn Error + Send>))
+        }));
+
+        Ok(())
+    }
+}
+
+fn use_singleton(new_singleton: Option<Singleton>) {
+    CURRENT_SINGLETON.with(|singleton| {
+        *singleton.borrow_mut() = new_singleton;
+    });
+}
+
+pub struct CoreGuard(Option<Singleton>);
+
+impl Drop for CoreGuard {
+    fn drop(&mut self) {
+        use_singleton(self.0.take())
+    }
+}
+
+#[doc(hidden)]
+pub unsafe fn _inner_use_core(core: Option<&Core>) -> CoreGuard {
+    let guard = get_singleton()
+        .map(|singleton| CoreGuard(Some(singleton)))
+        .unwrap_or(CoreGuard(None));
+
+    use_singleton(core.map(|core| core.singleton.private_clone()));
+
+    guard
+}
+
+pub struct CorePreserver<T>(pub T);
+
+impl<T> CorePreserver<T> {
+    pub fn spawn_with_handle<F: Future + Send + 'static>(
+        &self,
+        future: F,
+    ) -> Result<RemoteHandle<F::Output>, SpawnError>
+    where
+        T: Spawn,
+        F::Output: Send,
+    {
+        SpawnExt::spawn_with_handle(self, future)
+    }
+
+    pub fn spawn_local_with_handle<F: Future + 'static>(
+        &self,
+        future: F,
+    ) -> Result<RemoteHandle<F::Output>, SpawnError>
+    where
+        T: LocalSpawn,
+    {
+        LocalSpawnExt::spawn_local_with_handle(self, future)
+    }
+}
+
+/// Future or stream that installs a core as the current core whenever it is polled, see
+/// `CoreFutureExt::in_core` and `CoreStreamExt::in_core`.
+pub struct InCore<T> {
+    inner: T,
+    core: Option<Core>,
+}
+
+impl<T> InCore<T> {
+    fn new(inner: T, core: Option<Core>) -> Self {
+        InCore { inner, core }
+    }
+
+    fn project(self: Pin<&mut Self>) -> (Pin<&mut T>, &Option<Core>) {
+        // inner is structurally pinned, the core is never pinned and InCore has no Drop impl
+        unsafe {
+            let this = self.get_unchecked_mut();
+            (Pin::new_unchecked(&mut this.inner), &this.core)
+        }
+    }
+}
+
+impl<T: Future> Future for InCore<T> {
+    type Output = T::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let (inner, core) = self.project();
+        let guard = unsafe { _inner_use_core(core.as_ref()) };
+        let output = inner.poll(cx);
+        drop(guard);
+        output
+    }
+}
+
+impl<T: Stream> Stream for InCore<T> {
+    type Item = T::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let (inner, core) = self.project();
+        let guard = unsafe { _inner_use_core(core.as_ref()) };
+        let output = inner.poll_next(cx);
+        drop(guard);
+        output
+    }
+}
+
+pub trait CoreFutureExt: Future {
+    fn in_core(self, core: &Core) -> InCore<Self>
+    where
+        Self: Sized,
+    {
+        InCore::new(
+            self,
+            Some(Core {
+                singleton: core.singleton.private_clone(),
+            }),
+        )
+    }
+}
+
+impl<T: Future> CoreFutureExt for T {}
+
+pub trait CoreStreamExt: Stream {
+    fn in_core(self, core: &Core) -> InCore<Self>
+    where
+        Self: Sized,
+    {
+        InCore::new(
+            self,
+            Some(Core {
+                singleton: core.singleton.private_clone(),
+            }),
+        )
+    }
+}
+
+impl<T: Stream> CoreStreamExt for T {}
+
+impl<T: Spawn> Spawn for CorePreserver<T> {
+    fn spawn_obj(&self, future: FutureObj<'static, ()>) -> Result<(), SpawnError> {
+        let core = get_singleton().map(|singleton| Core { singleton });
+        self.0.spawn_obj(Box::pin(InCore::new(future, core)).into())
+    }
+}
+
+impl<T: LocalSpawn> LocalSpawn for CorePreserver<T> {
+    fn spawn_local_obj(&self, future: LocalFutureObj<'static, ()>) -> Result<(), SpawnError> {
+        let core = get_singleton().map(|singleton| Core { singleton });
+        self.0
+            .spawn_local_obj(Box::pin(InCore::new(future, core)).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        channel::oneshot,
+        executor::{block_on, LocalPool},
+        poll,
+    };
+    use std::{panic, rc::Rc, sync::Barrier, thread, time::Duration};
+
+    #[derive(Debug, Error)]
+    #[error("factory failed")]
+    struct Failed;
+
+    // defers creating the future until the core is installed, as the free functions look it up
+    // when called rather than when polled
+    fn run<F: Future, C: FnOnce() -> F>(core: &Core, future: C) -> F::Output {
+        block_on(async move { future().await }.in_core(core))
+    }
+
+    fn counting(count: &Arc<AtomicUsize>) -> impl Fn() -> Ready<Result<usize, Failed>> + Send {
+        let count = count.clone();
+        move || ready(Ok(count.fetch_add(1, Ordering::SeqCst)))
+    }
+
+    #[test]
+    fn singleton_factory_runs_once_under_concurrent_acquisition() {
+        let core = Core::new();
+        let count = Arc::new(AtomicUsize::new(0));
+        let factory = count.clone();
+
+        run(&core, || {
+            register_with(Lifetime::Singleton, move || {
+                let count = factory.clone();
+                async move {
+                    thread::sleep(Duration::from_millis(20));
+                    Ok::<_, Failed>(count.fetch_add(1, Ordering::SeqCst))
+                }
+            })
+        })
+        .unwrap();
+
+        let barrier = Arc::new(Barrier::new(8));
+        let threads = (0..8)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let acquire = async { acquire::<Arc<usize>>().await }.in_core(&core);
+
+                thread::spawn(move || {
+                    barrier.wait();
+                    block_on(acquire).unwrap().unwrap()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let instances = threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(instances
+            .iter()
+            .all(|instance| Arc::ptr_eq(instance, &instances[0])));
+    }
+
+    #[test]
+    fn transient_creates_an_instance_per_acquisition() {
+        let core = Core::new();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        run(&core, || async {
+            register(counting(&count)).await.unwrap();
+            register_with(Lifetime::Transient, || ready(Ok::<_, Failed>(0u8)))
+                .await
+                .unwrap();
+
+            assert_eq!(acquire::<usize>().await.unwrap(), Some(0));
+            assert_eq!(acquire::<usize>().await.unwrap(), Some(1));
+
+            let a = acquire::<Arc<u8>>().await.unwrap().unwrap();
+            let b = acquire::<Arc<u8>>().await.unwrap().unwrap();
+            assert!(!Arc::ptr_eq(&a, &b));
+        });
+    }
+
+    #[test]
+    fn scoped_instances_are_shared_within_a_scope() {
+        let core = Core::new();
+        let count = Arc::new(AtomicUsize::new(0));
+
+        run(&core, || register_with(Lifetime::Scoped, counting(&count))).unwrap();
+
+        let first = core.scope();
+        let second = core.scope();
+
+        let a = run(&first, acquire::<Arc<usize>>).unwrap().unwrap();
+        let b = run(&first, acquire::<Arc<usize>>).unwrap().unwrap();
+        let c = run(&second, acquire::<Arc<usize>>).unwrap().unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert_eq!(count.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn slow_factories_do_not_block_other_acquisitions() {
+        let core = Core::new();
+        let (sender, receiver) = oneshot::channel::<()>();
+        let receiver = Arc::new(Mutex::new(Some(receiver)));
+
+        run(&core, || async {
+            register_with(Lifetime::Singleton, move || {
+                let receiver = receiver.clone();
+                async move {
+                    let receiver = receiver.lock().await.take().unwrap();
+                    receiver.await.unwrap();
+                    Ok::<_, Failed>(1u32)
+                }
+            })
+            .await
+            .unwrap();
+            register(|| ready(Ok::<_, Failed>(2u64))).await.unwrap();
+
+            let mut slow = Box::pin(acquire::<Arc<u32>>());
+            assert!(poll!(&mut slow).is_pending());
+
+            assert_eq!(acquire::<u64>().await.unwrap(), Some(2));
+            register(|| ready(Ok::<_, Failed>(3u16))).await.unwrap();
+            assert_eq!(acquire::<u16>().await.unwrap(), Some(3));
+
+            sender.send(()).unwrap();
+            assert_eq!(*slow.await.unwrap().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn factories_can_acquire_other_registrations() {
+        let core = Core::new();
+
+        let item = run(&core, || async {
+            register(|| async { Ok::<_, CoreError>(u64::from(require::<u16>().await?)) })
+                .await
+                .unwrap();
+            register(|| ready(Ok::<_, Failed>(7u16))).await.unwrap();
+
+            acquire::<u64>().await
+        });
+
+        assert_eq!(item.unwrap(), Some(7));
+    }
+
+    #[test]
+    fn self_acquisition_is_a_cycle() {
+        let core = Core::new();
+
+        let error = run(&core, || async {
+            register(|| async { require::<u32>().await }).await.unwrap();
+
+            acquire::<u32>().await
+        })
+        .unwrap_err();
+
+        assert!(matches!(error, CoreError::Cycle(cycle) if cycle == vec!["u32", "u32"]));
+    }
+
+    #[test]
+    fn cycles_are_reported_through_every_factory() {
+        let core = Core::new();
+
+        let error = run(&core, || async {
+            register(|| async { Ok::<_, CoreError>(u32::from(require::<u16>().await?)) })
+                .await
+                .unwrap();
+            register(|| async { Ok::<_, CoreError>(require::<u32>().await? as u16) })
+                .await
+                .unwrap();
+
+            acquire::<u32>().await
+        })
+        .unwrap_err();
+
+        assert!(matches!(error, CoreError::Cycle(cycle) if cycle == vec!["u32", "u16", "u32"]));
+    }
+
+    #[test]
+    fn panicking_factories_are_not_cycles() {
+        let core = Core::new();
+        let panicked = Arc::new(AtomicBool::new(false));
+
+        run(&core, || async move {
+            register_with(Lifetime::Transient, move || {
+                let panicked = panicked.clone();
+
+                async move {
+                    if !panicked.swap(true, Ordering::SeqCst) {
+                        panic!("factory panicked");
+                    }
+
+                    Ok::<_, Failed>(7u32)
+                }
+            })
+            .await
+        })
+        .unwrap();
+
+        let panic = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            run(&core, || async { acquire::<Arc<u32>>().await })
+        }));
+
+        assert!(panic.is_err());
+        assert_eq!(
+            *run(&core, || async { require::<Arc<u32>>().await }).unwrap(),
+            7
+        );
+    }
+
+    trait Storage: Send + Sync {
+        fn name(&self) -> &'static str;
+    }
+
+    struct Disk;
+
+    impl Storage for Disk {
+        fn name(&self) -> &'static str {
+            "disk"
+        }
+    }
+
+    #[test]
+    fn interfaces_are_acquired_as_arc_or_box() {
+        let core = Core::new();
+
+        run(&core, || async {
+            register_as::<dyn Storage, _, _, _>(Lifetime::Singleton, || {
+                ready(Ok::<_, Failed>(Box::new(Disk) as Box<dyn Storage>))
+            })
+            .await
+            .unwrap();
+            register(|| ready(Ok::<_, Failed>(Box::new(Disk) as Box<dyn Storage>)))
+                .await
+                .unwrap();
+
+            let shared = acquire::<Arc<dyn Storage>>().await.unwrap().unwrap();
+            let boxed = acquire::<Box<dyn Storage>>().await.unwrap().unwrap();
+
+            assert_eq!(shared.name(), "disk");
+            assert_eq!(boxed.name(), "disk");
+
+            replace_as::<dyn Storage, _, _, _>(Lifetime::Singleton, || {
+                ready(Ok::<_, Failed>(Box::new(Disk) as Box<dyn Storage>))
+            })
+            .await
+            .unwrap();
+            assert!(!Arc::ptr_eq(
+                &shared,
+                &acquire::<Arc<dyn Storage>>().await.unwrap().unwrap()
+            ));
+        });
+    }
+
+    #[derive(Clone)]
+    enum Supply {
+        Item(u32),
+        Nothing,
+        Failure,
+        Mistyped,
+    }
+
+    impl Provider for Supply {
+        fn acquire(
+            &self,
+            ty: TypeId,
+            key: Option<String>,
+        ) -> Pin<
+            Box<
+                dyn Future<Output = Result<Option<Box<dyn Any + Send>>, Box<dyn Error + Send>>>
+                    + Send,
+            >,
+        > {
+            let item = if ty != TypeId::of::<u32>() || key.is_some() {
+                Ok(None)
+            } else {
+                match self {
+                    Supply::Item(item) => Ok(Some(Box::new(*item) as Box<dyn Any + Send>)),
+                    Supply::Nothing => Ok(None),
+                    Supply::Failure => Err(Box::new(Failed) as Box<dyn Error + Send>),
+                    Supply::Mistyped => Ok(Some(Box::new(0u64) as Box<dyn Any + Send>)),
+                }
+            };
+
+            Box::pin(ready(item))
+        }
+
+        fn box_clone(&self) -> Box<dyn Provider + Send> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn supplied(providers: Vec<Supply>) -> Core {
+        let core = Core::new();
+
+        for provider in providers {
+            block_on(core.add_provider(Box::new(provider)));
+        }
+
+        core
+    }
+
+    #[test]
+    fn first_supplying_provider_wins() {
+        let core = supplied(vec![Supply::Item(1), Supply::Item(2)]);
+
+        assert_eq!(run(&core, acquire::<u32>).unwrap(), Some(1));
+        assert_eq!(run(&core, acquire::<u64>).unwrap(), None);
+    }
+
+    #[test]
+    fn providers_without_an_item_fall_through() {
+        let core = supplied(vec![Supply::Nothing, Supply::Item(2)]);
+
+        assert_eq!(run(&core, acquire::<u32>).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn provider_errors_surface() {
+        let core = supplied(vec![Supply::Failure, Supply::Item(2)]);
+
+        match run(&core, acquire::<u32>) {
+            Err(CoreError::Provider { ty, .. }) => assert_eq!(ty, "u32"),
+            result => panic!("expected a provider error, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn mistyped_provider_items_are_rejected() {
+        let core = supplied(vec![Supply::Mistyped]);
+
+        match run(&core, acquire::<u32>) {
+            Err(CoreError::TypeMismatch(ty)) => assert_eq!(ty, "u32"),
+            result => panic!("expected a type mismatch, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn registrations_shadow_providers() {
+        let core = supplied(vec![Supply::Item(1)]);
+
+        let item = run(&core, || async {
+            register(|| ready(Ok::<_, Failed>(3u32))).await.unwrap();
+
+            acquire::<u32>().await
+        });
+
+        assert_eq!(item.unwrap(), Some(3));
+    }
+
+    #[test]
+    fn shutting_down_a_scope_leaves_its_core_intact() {
+        let core = Core::new();
+        let scope = core.scope();
+        let disposed = Arc::new(AtomicUsize::new(0));
+        let hook = disposed.clone();
+
+        run(&core, || register(|| ready(Ok::<_, Failed>(1u32)))).unwrap();
+        run(&scope, || {
+            on_shutdown(move || {
+                hook.fetch_add(1, Ordering::SeqCst);
+                ready(Ok::<_, Failed>(()))
+            })
+        })
+        .unwrap();
+
+        block_on(scope.shutdown()).unwrap();
+
+        assert_eq!(disposed.load(Ordering::SeqCst), 1);
+        assert!(matches!(
+            run(&scope, acquire::<u32>),
+            Err(CoreError::TornDown(_))
+        ));
+        assert_eq!(run(&core, acquire::<u32>).unwrap(), Some(1));
+        assert_eq!(run(&core.scope(), acquire::<u32>).unwrap(), Some(1));
+
+        block_on(core.shutdown()).unwrap();
+
+        assert!(matches!(
+            run(&core.scope(), acquire::<u32>),
+            Err(CoreError::TornDown(_))
+        ));
+    }
+
+    #[test]
+    fn torn_down_cores_reject_registrations_and_hooks() {
+        let core = Core::new();
+
+        block_on(core.shutdown()).unwrap();
+
+        assert!(matches!(
+            run(&core, || register(|| ready(Ok::<_, Failed>(1u32)))),
+            Err(CoreError::TornDown(_))
+        ));
+        assert!(matches!(
+            run(&core, || on_shutdown(|| ready(Ok::<_, Failed>(())))),
+            Err(CoreError::TornDown(_))
+        ));
+    }
+
+    #[test]
+    fn replacements_reach_every_scope() {
+        let core = Core::new();
+        let scope = core.scope();
+
+        run(&core, || {
+            register_with(Lifetime::Scoped, || ready(Ok::<_, Failed>(1u32)))
+        })
+        .unwrap();
+        assert_eq!(*run(&scope, acquire::<Arc<u32>>).unwrap().unwrap(), 1);
+
+        run(&core, || {
+            replace_with(Lifetime::Scoped, || ready(Ok::<_, Failed>(2u32)))
+        })
+        .unwrap();
+        assert_eq!(*run(&scope, acquire::<Arc<u32>>).unwrap().unwrap(), 2);
+
+        run(&scope, unregister::<Arc<u32>>).unwrap();
+        assert_eq!(run(&core, acquire::<Arc<u32>>).unwrap(), None);
+    }
+
+    #[test]
+    fn named_registrations_can_be_replaced_and_watched() {
+        let core = Core::new();
+
+        run(&core, || async {
+            let mut changes = watch_named::<u32, _>("primary").await.unwrap();
+
+            assert!(matches!(
+                replace_named("primary", || ready(Ok::<_, Failed>(1u32))).await,
+                Err(CoreError::NotRegistered(_))
+            ));
+
+            register_named("primary", || ready(Ok::<_, Failed>(1u32)))
+                .await
+                .unwrap();
+            replace_named("primary", || ready(Ok::<_, Failed>(2u32)))
+                .await
+                .unwrap();
+            assert_eq!(acquire_named::<u32, _>("primary").await.unwrap(), Some(2));
+            assert!(unregister_named::<u32, _>("primary").await.unwrap());
+
+            assert_eq!(changes.next().await, Some(Change::Registered));
+            assert_eq!(changes.next().await, Some(Change::Replaced));
+            assert_eq!(changes.next().await, Some(Change::Unregistered));
+        });
+    }
+
+    #[test]
+    fn children_resolve_through_their_parent() {
+        let parent = Core::new();
+        let child = Core::child(&parent);
+
+        run(&parent, || register(|| ready(Ok::<_, Failed>(1u32)))).unwrap();
+
+        assert_eq!(run(&child, acquire::<u32>).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn children_override_without_affecting_their_parent() {
+        let parent = Core::new();
+        let child = Core::child(&parent);
+
+        run(&parent, || register(|| ready(Ok::<_, Failed>(1u32)))).unwrap();
+        run(&child, || async {
+            register(|| ready(Ok::<_, Failed>(2u32))).await?;
+            register(|| ready(Ok::<_, Failed>(3u64))).await
+        })
+        .unwrap();
+
+        assert_eq!(run(&child, acquire::<u32>).unwrap(), Some(2));
+        assert_eq!(run(&child, acquire::<u64>).unwrap(), Some(3));
+        assert_eq!(run(&parent, acquire::<u32>).unwrap(), Some(1));
+        assert_eq!(run(&parent, acquire::<u64>).unwrap(), None);
+    }
+
+    #[test]
+    fn child_cores_are_preserved() {
+        let parent = Core::new();
+        let child = Core::child(&parent);
+        let mut pool = LocalPool::new();
+        let spawner = CorePreserver(pool.spawner());
+
+        run(&parent, || register(|| ready(Ok::<_, Failed>(1u32)))).unwrap();
+        run(&child, || register(|| ready(Ok::<_, Failed>(2u64)))).unwrap();
+
+        let handle;
+
+        with_core!(&child => {
+            handle = spawner
+                .spawn_with_handle(async {
+                    (acquire::<u32>().await.unwrap(), acquire::<u64>().await.unwrap())
+                })
+                .unwrap();
+        });
+
+        assert_eq!(pool.run_until(handle), (Some(1), Some(2)));
+    }
+
+    #[test]
+    fn registrations_are_described() {
+        let parent = Core::new();
+        let child = Core::child(&parent);
+
+        run(&parent, || async {
+            register_with(Lifetime::Singleton, || ready(Ok::<_, Failed>(1u32))).await?;
+            register_named("failing", || ready(Err::<u32, _>(Failed))).await
+        })
+        .unwrap();
+        run(&child, || async {
+            register_with(Lifetime::Transient, || ready(Ok::<_, Failed>(2u64))).await?;
+            acquire::<Arc<u64>>().await?;
+            acquire::<Arc<u64>>().await?;
+            acquire::<Arc<u32>>().await?;
+            acquire::<Arc<u32>>().await?;
+            acquire_named::<u32, _>("failing").await
+        })
+        .unwrap_err();
+
+        let descriptions = block_on(child.describe());
+
+        assert_eq!(descriptions.len(), 3);
+
+        let transient = &descriptions[0];
+        assert_eq!(transient.ty, type_name::<Arc<u64>>());
+        assert_eq!(transient.lifetime, Lifetime::Transient);
+        assert_eq!(transient.created, 2);
+        assert_eq!(transient.depth, 0);
+
+        let singleton = &descriptions[1];
+        assert_eq!(singleton.ty, type_name::<Arc<u32>>());
+        assert_eq!(singleton.key, None);
+        assert_eq!(singleton.lifetime, Lifetime::Singleton);
+        assert_eq!(singleton.created, 1);
+        assert_eq!(singleton.last_error, None);
+        assert_eq!(singleton.depth, 1);
+
+        let failing = &descriptions[2];
+        assert_eq!(failing.key.as_deref(), Some("failing"));
+        assert_eq!(failing.lifetime, Lifetime::Transient);
+        assert_eq!(failing.created, 0);
+        assert_eq!(failing.last_error.as_deref(), Some("factory failed"));
+        assert_eq!(failing.depth, 1);
+
+        assert_eq!(block_on(parent.describe()).len(), 2);
+    }
+
+    #[test]
+    fn trace_hooks_observe_registrations_and_acquisitions() {
+        let core = Core::new();
+        let events = Arc::new(std::sync::Mutex::new(vec![]));
+        let trace = events.clone();
+
+        block_on(core.set_trace(move |event| {
+            trace.lock().unwrap().push(match event {
+                Event::Register { ty, key } => format!("register {} {:?}", ty, key),
+                Event::Unregister { ty, key } => format!("unregister {} {:?}", ty, key),
+                Event::Acquire { ty, key, result } => match result {
+                    Ok(found) => format!("acquire {} {:?} {}", ty, key, found),
+                    Err(error) => format!("acquire {} {:?} {}", ty, key, error),
+                },
+            })
+        }));
+
+        run(&core, || async {
+            register_named("primary", || ready(Ok::<_, Failed>(1u32))).await?;
+            acquire_named::<u32, _>("primary").await?;
+            acquire::<u32>().await?;
+            unregister::<u32>().await?;
+            unregister_named::<u32, _>("primary").await?;
+            register(|| ready(Err::<u64, _>(Failed))).await?;
+            acquire::<u64>().await
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "register u32 Some(\"primary\")",
+                "acquire u32 Some(\"primary\") true",
+                "acquire u32 None false",
+                "unregister u32 Some(\"primary\")",
+                "register u64 None",
+                "acquire u64 None factory for u64 failed: factory failed",
+            ]
+        );
+    }
+
+    #[test]
+    fn local_tasks_run_in_the_spawning_core() {
+        let core = Core::new();
+        let mut pool = LocalPool::new();
+        let spawner = CorePreserver(pool.spawner());
+        let (sender, receiver) = oneshot::channel();
+
+        run(&core, || register(|| ready(Ok::<_, Failed>(1u32)))).unwrap();
+
+        let handle;
+
+        with_core!(&core => {
+            // not `Send`, so this can only be spawned locally
+            let local = Rc::new(2u32);
+
+            handle = spawner
+                .spawn_local_with_handle(async move {
+                    Some(*local + acquire::<u32>().await.unwrap()?)
+                })
+                .unwrap();
+            spawner
+                .spawn_local(async move {
+                    sender.send(acquire::<u32>().await.unwrap()).unwrap();
+                })
+                .unwrap();
+        });
+
+        assert!(get_singleton().is_none());
+        assert_eq!(pool.run_until(handle), Some(3));
+        pool.run();
+        assert_eq!(block_on(receiver).unwrap(), Some(1));
+    }
+
+    #[test]
+    fn shutdown_is_reported_to_watchers() {
+        let core = Core::new();
+
+        let (mut plain, mut shared) = run(&core, || async {
+            let watches = (watch::<u32>().await?, watch::<Arc<u64>>().await?);
+
+            register(|| ready(Ok::<_, Failed>(1u32))).await?;
+            register_with(Lifetime::Singleton, || ready(Ok::<_, Failed>(2u64))).await?;
+
+            Ok::<_, CoreError>(watches)
+        })
+        .unwrap();
+
+        block_on(core.shutdown()).unwrap();
+
+        block_on(async {
+            assert_eq!(plain.next().await, Some(Change::Registered));
+            assert_eq!(plain.next().await, Some(Change::Unregistered));
+            assert_eq!(shared.next().await, Some(Change::Registered));
+            assert_eq!(shared.next().await, Some(Change::Unregistered));
+        });
+    }
+
+    #[cfg(feature = "ring-sha256")]
+    #[test]
+    fn ring_hashes_incrementally() {
+        let data = (0..1 << 20).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = ring::digest::digest(&SHA256, &data);
+
+        let mut hasher = Ring::new();
+
+        for chunk in data.chunks(4096 + 7) {
+            hasher.write(chunk);
+        }
+
+        assert_eq!(&hasher.hash().0[..], expected.as_ref());
+        assert_eq!(hasher.hash(), hasher.hash());
+
+        hasher.write(b"more");
+        assert_ne!(&hasher.hash().0[..], expected.as_ref());
+    }
+
+    // hashes `data` in uneven pieces, which must not affect the result
+    #[cfg(any(feature = "ring-sha512", feature = "blake3"))]
+    fn split<A: Algorithm, H: Hasher<A>>(data: &[u8]) -> A::Hash {
+        let mut hasher = H::new();
+
+        for chunk in data.chunks(3) {
+            hasher.write(chunk);
+        }
+
+        hasher.hash()
+    }
+
+    #[cfg(feature = "ring-sha512")]
+    #[test]
+    fn ring_sha512_matches_known_answers() {
+        let empty = "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+                     47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e";
+        let abc = "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+                   2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f";
+
+        assert_eq!(split::<_, RingSha512>(b""), empty.parse().unwrap());
+        assert_eq!(split::<_, RingSha512>(b"abc"), abc.parse().unwrap());
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_matches_known_answers() {
+        let empty = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+        let abc = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+
+        assert_eq!(split::<_, Blake3Hasher>(b""), empty.parse().unwrap());
+        assert_eq!(split::<_, Blake3Hasher>(b"abc"), abc.parse().unwrap());
+    }
+
+    const ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn digests_format_and_parse() {
+        let sum = ABC.parse::<Sha256Sum>().unwrap();
+        let base32 = format!("{:#}", sum);
+
+        assert_eq!(sum.to_string(), ABC);
+        assert_eq!(
+            base32,
+            "xj4bnp4pahh6uqkbidpf3lrceoyagyndsylxvhfucd7wd4qacwwq"
+        );
+        assert_eq!(base32.parse::<Sha256Sum>().unwrap(), sum);
+        assert_eq!(format!("{:?}", sum), format!("Sha256Sum({})", ABC));
+    }
+
+    #[test]
+    fn digests_serialize_as_bytes_or_strings() {
+        use serde::de::{value::Error as ValueError, IntoDeserializer};
+
+        let sum = ABC.parse::<Sha256Sum>().unwrap();
+        let cbor = to_vec(&sum).unwrap();
+
+        // a CBOR byte string rather than an array of integers
+        assert_eq!(&cbor[..2], &[0x58, 32]);
+        assert_eq!(&cbor[2..], &sum.0[..]);
+        assert_eq!(from_slice::<Sha256Sum>(&cbor).unwrap(), sum);
+
+        let readable =
+            Sha256Sum::deserialize(IntoDeserializer::<ValueError>::into_deserializer(ABC));
+        assert_eq!(readable.unwrap(), sum);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn digests_serialize_as_hex_when_human_readable() {
+        let sum = ABC.parse::<Sha256Sum>().unwrap();
+        let json = serde_json::to_string(&sum).unwrap();
+
+        assert_eq!(json, format!("\"{}\"", ABC));
+        assert_eq!(serde_json::from_str::<Sha256Sum>(&json).unwrap(), sum);
     }
 }
@@ -1,14 +1,19 @@
 use crate::{
     resource::{
         hash::{Algorithm, Hasher},
-        provider::ResourceProvider,
-        Rehydrate,
+        provider::{ByteStream, ResourceProvider, StreamingResourceProvider},
+        Rehydrate, StreamingRehydrate,
     },
     Resource,
 };
 use core_error::Error;
-use futures::{lock::Mutex, Future};
-use std::{collections::HashMap, hash::Hash, pin::Pin, sync::Arc};
+use futures::{
+    future::ready,
+    lock::Mutex,
+    stream::{once, StreamExt},
+    Future,
+};
+use std::{collections::HashMap, convert::Infallible, hash::Hash, pin::Pin, sync::Arc};
 
 pub struct MemoryStore<A: Algorithm> {
     data: Arc<Mutex<HashMap<A::Hash, Vec<u8>>>>,
@@ -57,6 +62,38 @@ impl<A: Algorithm> MemoryStore<A> {
             Ok(Resource::new(hash))
         }
     }
+
+    /// Like `intern`, but dumps `item` with `StreamingRehydrate::dump_stream`, hashing each chunk
+    /// as it arrives instead of hashing the assembled buffer afterwards.
+    pub fn intern_stream<H: Hasher<A>, T, U: StreamingRehydrate<T>>(
+        &mut self,
+        item: T,
+    ) -> impl Future<Output = Result<Resource<T, U, A>, Box<dyn Error + Send>>>
+    where
+        A::Hash: Eq + Hash + Clone,
+        U::DumpError: Error + Send + 'static,
+    {
+        let data = self.data.clone();
+
+        async move {
+            let mut chunks = Box::pin(U::dump_stream(item));
+            let mut hasher = H::new();
+            let mut item = vec![];
+
+            while let Some(chunk) = chunks.next().await {
+                let chunk = chunk.map_err(|e| Box::new(e) as Box<dyn Error + Send>)?;
+
+                hasher.write(&chunk);
+                item.extend_from_slice(&chunk);
+            }
+
+            let hash = hasher.hash();
+
+            data.lock().await.insert(hash.clone(), item);
+
+            Ok(Resource::new(hash))
+        }
+    }
 }
 
 impl<A: Algorithm> ResourceProvider<A> for MemoryStore<A>
@@ -76,3 +113,23 @@ where
         })
     }
 }
+
+impl<A: Algorithm> StreamingResourceProvider<A> for MemoryStore<A>
+where
+    A::Hash: Hash + Eq + Send + 'static,
+{
+    type FetchStream = Pin<Box<dyn Future<Output = Result<Option<ByteStream>, Infallible>> + Send>>;
+
+    fn fetch_stream(&self, hash: A::Hash) -> Self::FetchStream {
+        let data = self.data.clone();
+
+        Box::pin(async move {
+            let data = data.lock().await;
+
+            Ok(data
+                .get(&hash)
+                .cloned()
+                .map(|data| Box::pin(once(ready(Ok(data)))) as ByteStream))
+        })
+    }
+}
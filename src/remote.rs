@@ -0,0 +1,236 @@
+use crate::{Core, CoreError, Provider, Singleton};
+use core::{
+    any::{Any, TypeId},
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+};
+use core_error::Error;
+use futures::{future::ready, lock::Mutex, TryFuture, TryFutureExt};
+use protocol::protocol;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[protocol]
+pub trait RemoteService<T> {
+    type Acquire: TryFuture<Ok = Option<T>>;
+
+    fn acquire(&self, key: Option<String>) -> Self::Acquire;
+}
+
+pub type ErasedRemoteService<T, E> = Box<
+    dyn RemoteService<T, Acquire = Pin<Box<dyn Future<Output = Result<Option<T>, E>> + Send>>>
+        + Send,
+>;
+
+pub type ErrorErasedRemoteService<T> = ErasedRemoteService<T, Box<dyn Error + Send>>;
+
+#[derive(Debug, Error)]
+pub enum RemoteError {
+    #[error("acquisition of {0:?} is not permitted for this vessel")]
+    Denied(Option<String>),
+    #[error("core error: {0}")]
+    Core(#[source] CoreError),
+}
+
+/// Host side of the bridge, exposing the registrations for `T` in a core that are on its
+/// allowlist. Nothing is permitted until `allow` or `allow_named` is called.
+pub struct Expose<T> {
+    singleton: Singleton,
+    allowed: Vec<Option<String>>,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<T> Expose<T> {
+    pub fn new(core: &Core) -> Self {
+        Expose {
+            singleton: core.singleton.private_clone(),
+            allowed: vec![],
+            ty: PhantomData,
+        }
+    }
+
+    pub fn allow(mut self) -> Self {
+        self.allowed.push(None);
+        self
+    }
+
+    pub fn allow_named<K: Into<String>>(mut self, key: K) -> Self {
+        self.allowed.push(Some(key.into()));
+        self
+    }
+}
+
+impl<T: Any + Send> RemoteService<T> for Expose<T> {
+    type Acquire = Pin<Box<dyn Future<Output = Result<Option<T>, RemoteError>> + Send>>;
+
+    fn acquire(&self, key: Option<String>) -> Self::Acquire {
+        if !self.allowed.contains(&key) {
+            return Box::pin(ready(Err(RemoteError::Denied(key))));
+        }
+
+        Box::pin(self.singleton.acquire::<T>(key).map_err(RemoteError::Core))
+    }
+}
+
+struct RemoteServiceEraser<T, S: RemoteService<T>> {
+    service: S,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<T, S: RemoteService<T>> RemoteService<T> for RemoteServiceEraser<T, S>
+where
+    S::Acquire: Send + 'static,
+    <S::Acquire as TryFuture>::Error: Error + Send + 'static,
+{
+    type Acquire = Pin<Box<dyn Future<Output = Result<Option<T>, Box<dyn Error + Send>>> + Send>>;
+
+    fn acquire(&self, key: Option<String>) -> Self::Acquire {
+        Box::pin(
+            self.service
+                .acquire(key)
+                .map_err(|e| Box::new(e) as Box<dyn Error + Send>),
+        )
+    }
+}
+
+pub trait RemoteServiceExt<T>: RemoteService<T> {
+    fn erase(self) -> ErrorErasedRemoteService<T>
+    where
+        Self: Sized + Send + 'static,
+        Self::Acquire: Send + 'static,
+        <Self::Acquire as TryFuture>::Error: Error + Send + 'static,
+        T: 'static,
+    {
+        Box::new(RemoteServiceEraser {
+            service: self,
+            ty: PhantomData,
+        })
+    }
+}
+
+impl<T, S: RemoteService<T>> RemoteServiceExt<T> for S {}
+
+/// Guest side of the bridge. Added to a core with `Core::add_provider`, it makes `acquire::<T>()`
+/// and `acquire_named::<T, _>(key)` resolve through the remote service whenever the guest has no
+/// registration of its own.
+///
+/// The host hands its `Expose` to the guest erased, as an `ErrorErasedRemoteService`, which is
+/// the form `#[protocol]` carries over the vessel transport as part of the unravelled object.
+/// `from_erased` installs the service the guest receives.
+pub struct RemoteProvider<T> {
+    service: Arc<Mutex<ErrorErasedRemoteService<T>>>,
+}
+
+impl<T> Clone for RemoteProvider<T> {
+    fn clone(&self) -> Self {
+        RemoteProvider {
+            service: self.service.clone(),
+        }
+    }
+}
+
+impl<T: 'static> RemoteProvider<T> {
+    pub fn new<S: RemoteService<T> + Send + 'static>(service: S) -> Self
+    where
+        S::Acquire: Send + 'static,
+        <S::Acquire as TryFuture>::Error: Error + Send + 'static,
+    {
+        RemoteProvider::from_erased(service.erase())
+    }
+
+    pub fn from_erased(service: ErrorErasedRemoteService<T>) -> Self {
+        RemoteProvider {
+            service: Arc::new(Mutex::new(service)),
+        }
+    }
+}
+
+impl<T: Any + Send> Provider for RemoteProvider<T> {
+    fn acquire(
+        &self,
+        ty: TypeId,
+        key: Option<String>,
+    ) -> Pin<
+        Box<dyn Future<Output = Result<Option<Box<dyn Any + Send>>, Box<dyn Error + Send>>> + Send>,
+    > {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            if ty != TypeId::of::<T>() {
+                return Ok(None);
+            }
+
+            let acquire = service.lock().await.acquire(key);
+
+            acquire
+                .await
+                .map(|item| item.map(|item| Box::new(item) as Box<dyn Any + Send>))
+        })
+    }
+
+    fn box_clone(&self) -> Box<dyn Provider + Send> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{acquire, acquire_named, register, register_named, CoreFutureExt};
+    use futures::executor::block_on;
+
+    #[test]
+    fn named_acquisitions_reach_the_host() {
+        let host = Core::new();
+        let guest = Core::new();
+
+        block_on(
+            async {
+                register_named("primary", || ready(Ok::<_, RemoteError>(1u32))).await?;
+                register_named("secondary", || ready(Ok::<_, RemoteError>(2u32))).await
+            }
+            .in_core(&host),
+        )
+        .unwrap();
+
+        let expose = Expose::<u32>::new(&host).allow_named("primary");
+        block_on(guest.add_provider(Box::new(RemoteProvider::new(expose))));
+
+        block_on(
+            async {
+                assert_eq!(acquire_named::<u32, _>("primary").await.unwrap(), Some(1));
+
+                match acquire_named::<u32, _>("secondary").await {
+                    Err(CoreError::Provider { .. }) => {}
+                    result => panic!("expected the host to deny access, found {:?}", result),
+                }
+
+                assert!(acquire::<u32>().await.is_err());
+            }
+            .in_core(&guest),
+        );
+    }
+
+    #[test]
+    fn erased_services_round_trip() {
+        let host = Core::new();
+        let guest = Core::new();
+
+        block_on(async { register(|| ready(Ok::<_, RemoteError>(1u32))).await }.in_core(&host))
+            .unwrap();
+
+        // what the guest receives from the transport
+        let service: ErrorErasedRemoteService<u32> = Expose::<u32>::new(&host).allow().erase();
+
+        block_on(guest.add_provider(Box::new(RemoteProvider::from_erased(service))));
+
+        let item = block_on(async { acquire::<u32>().await }.in_core(&guest));
+        assert_eq!(item.unwrap(), Some(1));
+
+        let denied = Expose::<u32>::new(&host).erase();
+        let error = block_on(denied.acquire(None)).unwrap_err();
+
+        assert_eq!(error.to_string(), RemoteError::Denied(None).to_string());
+    }
+}
@@ -1,4 +1,4 @@
-use core::convert::TryInto;
+use core::{convert::TryInto, marker::PhantomData};
 
 pub trait Algorithm {
     type Hash;
@@ -33,3 +33,85 @@ pub trait HasherExt<A: Algorithm>: Hasher<A> {
 }
 
 impl<A: Algorithm, T: Hasher<A>> HasherExt<A> for T {}
+
+/// Two algorithms computed side by side in a single pass, for migrating content from one
+/// algorithm to another. `Resource::split` recovers a resource under each of them.
+pub struct Pair<A, B>(PhantomData<(A, B)>);
+
+impl<A: Algorithm, B: Algorithm> Algorithm for Pair<A, B> {
+    type Hash = (A::Hash, B::Hash);
+}
+
+impl<A: Algorithm, B: Algorithm, H: Hasher<A>, I: Hasher<B>> Hasher<Pair<A, B>> for (H, I) {
+    fn new() -> Self {
+        (H::new(), I::new())
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        self.0.write(data);
+        self.1.write(data);
+    }
+
+    fn hash(&self) -> (A::Hash, B::Hash) {
+        (self.0.hash(), self.1.hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        resource::{provider::ResourceProvider, Resource},
+        Cbor, MemoryStore, PortableSha256, Sha256,
+    };
+    use futures::executor::block_on;
+
+    // a stand-in second algorithm that only counts bytes
+    struct Length;
+
+    impl Algorithm for Length {
+        type Hash = usize;
+    }
+
+    struct Counter(usize);
+
+    impl Hasher<Length> for Counter {
+        fn new() -> Self {
+            Counter(0)
+        }
+
+        fn write(&mut self, data: &[u8]) {
+            self.0 += data.len();
+        }
+
+        fn hash(&self) -> usize {
+            self.0
+        }
+    }
+
+    #[test]
+    fn pairs_hash_with_both_algorithms() {
+        let item = vec![1u32, 2, 3];
+        let data = serde_cbor::to_vec(&item).unwrap();
+
+        let mut store = MemoryStore::<Pair<Sha256, Length>>::new();
+        let resource: Resource<Vec<u32>, Cbor, _> =
+            block_on(store.intern::<(PortableSha256, Counter), _, _>(item.clone())).unwrap();
+
+        let fetched = block_on(store.fetch(resource.hash())).unwrap();
+        assert_eq!(fetched, Some(data.clone()));
+
+        let (sha256, length) = resource.split();
+
+        let mut single = MemoryStore::<Sha256>::new();
+        let expected: Resource<Vec<u32>, Cbor, _> =
+            block_on(single.intern::<PortableSha256, _, _>(item)).unwrap();
+
+        assert_eq!(sha256.hash(), expected.hash());
+        assert_eq!(
+            sha256.hash(),
+            <PortableSha256 as HasherExt<_>>::hash(&data[..])
+        );
+        assert_eq!(length.hash(), data.len());
+    }
+}
@@ -1,14 +1,20 @@
-use super::{hash::Algorithm, Rehydrate};
+use super::{
+    hash::{Algorithm, Hasher},
+    multihash::{Multihash, Registry},
+    Chunks, Rehydrate, StreamingRehydrate,
+};
 use crate::{
     resource::{
-        provider::{ErrorErasedResourceProvider, ResourceProvider},
+        provider::{ByteStream, ErrorErasedResourceProvider, ResourceProvider},
         ResourceError,
     },
     Resource,
 };
 use futures::{
-    future::{ready, AndThen, Either, MapErr, MapOk, Ready},
-    Future, TryFuture, TryFutureExt,
+    channel::mpsc::{channel, Sender},
+    future::{join, ready, AndThen, Either, MapErr, MapOk, Ready},
+    stream::once,
+    Future, SinkExt, StreamExt, TryFuture, TryFutureExt,
 };
 use protocol::protocol;
 use std::{
@@ -17,6 +23,12 @@ use std::{
     pin::Pin,
 };
 
+pub type ErasedFetch =
+    Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>> + Send>>;
+
+pub type ErasedFetchStream =
+    Pin<Box<dyn Future<Output = Result<Option<ByteStream>, ResourceError<Infallible>>> + Send>>;
+
 pub trait ResourceManager {
     type Fetch: Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>>;
 
@@ -25,6 +37,43 @@ pub trait ResourceManager {
         algo: TypeId,
         hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
     ) -> Self::Fetch;
+
+    /// Fetches the data in chunks. By default it is fetched whole with `fetch` and yielded as a
+    /// single chunk.
+    fn fetch_stream(
+        &self,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+    ) -> ErasedFetchStream
+    where
+        Self::Fetch: Send + 'static,
+    {
+        Box::pin(
+            self.fetch(algo, hash)
+                .map_ok(|data| data.map(|data| Box::pin(once(ready(Ok(data)))) as ByteStream)),
+        )
+    }
+
+    /// Fetches data that `verify` accepts, failing with `ResourceError::HashMismatch` if there is
+    /// only data it rejects. By default only what `fetch` returns is considered.
+    fn fetch_verified(
+        &self,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        mut verify: Box<dyn FnMut(&[u8]) -> bool + Send>,
+    ) -> ErasedFetch
+    where
+        Self::Fetch: Send + 'static,
+    {
+        let fetch = self.fetch(algo, hash);
+
+        Box::pin(async move {
+            match fetch.await? {
+                Some(data) if !verify(&data) => Err(ResourceError::HashMismatch),
+                data => Ok(data),
+            }
+        })
+    }
 }
 
 impl<T: ?Sized + ResourceManager> ResourceManager for Box<T> {
@@ -37,15 +86,32 @@ impl<T: ?Sized + ResourceManager> ResourceManager for Box<T> {
     ) -> Self::Fetch {
         T::fetch(self, algo, hash)
     }
+
+    fn fetch_stream(
+        &self,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+    ) -> ErasedFetchStream
+    where
+        Self::Fetch: Send + 'static,
+    {
+        T::fetch_stream(self, algo, hash)
+    }
+
+    fn fetch_verified(
+        &self,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        verify: Box<dyn FnMut(&[u8]) -> bool + Send>,
+    ) -> ErasedFetch
+    where
+        Self::Fetch: Send + 'static,
+    {
+        T::fetch_verified(self, algo, hash, verify)
+    }
 }
 
-pub type ErasedResourceManager = Box<
-    dyn ResourceManager<
-            Fetch = Pin<
-                Box<dyn Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>> + Send>,
-            >,
-        > + Send,
->;
+pub type ErasedResourceManager = Box<dyn ResourceManager<Fetch = ErasedFetch> + Send>;
 
 pub struct ResourceManagerEraser<T: ResourceManager> {
     manager: T,
@@ -55,8 +121,7 @@ impl<T: ResourceManager> ResourceManager for ResourceManagerEraser<T>
 where
     T::Fetch: Send + 'static,
 {
-    type Fetch =
-        Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>> + Send>>;
+    type Fetch = ErasedFetch;
 
     fn fetch(
         &self,
@@ -65,55 +130,89 @@ where
     ) -> Self::Fetch {
         Box::pin(self.manager.fetch(algo, hash))
     }
-}
 
-pub trait ResourceManagerExt: ResourceManager {
-    fn into_erased(self) -> ErasedResourceManager
-    where
-        Self: Sized + Send + 'static,
-        Self::Fetch: Send,
-    {
-        Box::new(ResourceManagerEraser { manager: self })
+    fn fetch_stream(
+        &self,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+    ) -> ErasedFetchStream {
+        self.manager.fetch_stream(algo, hash)
     }
 
-    fn fetch<A: Algorithm + Any, T, U: Rehydrate<T>>(
+    fn fetch_verified(
         &self,
-        resource: Resource<T, U, A>,
-    ) -> AndThen<
-        MapErr<
-            <Self as ResourceManager>::Fetch,
-            fn(ResourceError<std::convert::Infallible>) -> ResourceError<U::RehydrateError>,
-        >,
-        Either<
-            MapErr<
-                MapOk<U::Rehydrate, fn(T) -> Option<T>>,
-                fn(U::RehydrateError) -> ResourceError<U::RehydrateError>,
-            >,
-            Ready<Result<Option<T>, ResourceError<U::RehydrateError>>>,
-        >,
+        algo: TypeId,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        verify: Box<dyn FnMut(&[u8]) -> bool + Send>,
+    ) -> ErasedFetch {
+        self.manager.fetch_verified(algo, hash, verify)
+    }
+}
+
+// feeds the decoder through a bounded channel. When `inspect` is given the stream is drained
+// even if the decoder stops reading early, so that every chunk passes through it
+async fn pump<F: FnMut(&[u8])>(
+    mut stream: ByteStream,
+    mut sender: Sender<Vec<u8>>,
+    mut inspect: Option<F>,
+) -> Result<(), Box<dyn core_error::Error + Send>> {
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        if let Some(inspect) = &mut inspect {
+            inspect(&chunk);
+        }
+
+        if sender.send(chunk).await.is_err() && inspect.is_none() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+async fn decode_stream<T, U: StreamingRehydrate<T>, F: FnMut(&[u8])>(
+    stream: ByteStream,
+    inspect: Option<F>,
+) -> Result<T, ResourceError<U::RehydrateError>> {
+    let (sender, receiver) = channel(1);
+
+    let (pumped, item) = join(
+        pump(stream, sender, inspect),
+        U::rehydrate_stream(Box::pin(receiver) as Chunks),
+    )
+    .await;
+
+    // a provider failing partway through takes precedence over the decoder's view of the data
+    pumped?;
+
+    item.map_err(ResourceError::Rehydration)
+}
+
+type Rehydrated<F, T, U> = AndThen<
+    MapErr<F, fn(ResourceError<Infallible>) -> ResourceError<<U as Rehydrate<T>>::RehydrateError>>,
+    Decode<T, U>,
+    fn(Option<Vec<u8>>) -> Decode<T, U>,
+>;
+
+type Decode<T, U> = Either<
+    MapErr<
+        MapOk<<U as Rehydrate<T>>::Rehydrate, fn(T) -> Option<T>>,
         fn(
-            Option<Vec<u8>>,
-        ) -> Either<
-            MapErr<
-                MapOk<U::Rehydrate, fn(T) -> Option<T>>,
-                fn(U::RehydrateError) -> ResourceError<U::RehydrateError>,
-            >,
-            Ready<Result<Option<T>, ResourceError<U::RehydrateError>>>,
-        >,
-    >
-    where
-        A::Hash: Clone + Send,
-        T: Send + 'static,
-        U: Send + 'static,
-    {
-        ResourceManager::fetch(
-            self,
-            TypeId::of::<A>(),
-            Box::new(move || Box::new(resource.hash())),
-        )
+            <U as Rehydrate<T>>::RehydrateError,
+        ) -> ResourceError<<U as Rehydrate<T>>::RehydrateError>,
+    >,
+    Ready<Result<Option<T>, ResourceError<<U as Rehydrate<T>>::RehydrateError>>>,
+>;
+
+fn rehydrate<F, T, U: Rehydrate<T>>(fetch: F) -> Rehydrated<F, T, U>
+where
+    F: Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>>,
+{
+    fetch
         .map_err(
             ResourceError::cast
-                as fn(ResourceError<std::convert::Infallible>) -> ResourceError<U::RehydrateError>,
+                as fn(ResourceError<Infallible>) -> ResourceError<U::RehydrateError>,
         )
         .and_then(
             (|data| {
@@ -129,14 +228,149 @@ pub trait ResourceManagerExt: ResourceManager {
                 } else {
                     Either::Right(ready(Ok(None)))
                 }
-            })
-                as fn(
-                    Option<Vec<u8>>,
-                ) -> Either<
-                    MapErr<_, _>,
-                    Ready<Result<Option<T>, ResourceError<U::RehydrateError>>>,
-                >,
+            }) as fn(Option<Vec<u8>>) -> Decode<T, U>,
         )
+}
+
+pub trait ResourceManagerExt: ResourceManager {
+    fn into_erased(self) -> ErasedResourceManager
+    where
+        Self: Sized + Send + 'static,
+        Self::Fetch: Send,
+    {
+        Box::new(ResourceManagerEraser { manager: self })
+    }
+
+    fn fetch<A: Algorithm + Any, T, U: Rehydrate<T>>(
+        &self,
+        resource: Resource<T, U, A>,
+    ) -> Rehydrated<<Self as ResourceManager>::Fetch, T, U>
+    where
+        A::Hash: Clone + Send,
+        T: Send + 'static,
+        U: Send + 'static,
+    {
+        rehydrate::<_, T, U>(ResourceManager::fetch(
+            self,
+            TypeId::of::<A>(),
+            Box::new(move || Box::new(resource.hash())),
+        ))
+    }
+
+    /// Fetches the raw data for a multihash, using `registry` to find the `Algorithm` it was
+    /// produced with.
+    fn fetch_multihash(
+        &self,
+        registry: &Registry,
+        multihash: &Multihash,
+    ) -> Either<
+        <Self as ResourceManager>::Fetch,
+        Ready<Result<Option<Vec<u8>>, ResourceError<Infallible>>>,
+    > {
+        match registry.resolve(multihash) {
+            Ok((algo, hash)) => Either::Left(ResourceManager::fetch(self, algo, hash)),
+            Err(e) => Either::Right(ready(Err(ResourceError::Multihash(e)))),
+        }
+    }
+
+    /// Like `fetch`, but rehashes the data with `H` and only accepts it if it matches
+    /// `resource.hash()`, failing with `ResourceError::HashMismatch` otherwise.
+    fn fetch_verified<A: Algorithm + Any, H: Hasher<A>, T, U: Rehydrate<T>>(
+        &self,
+        resource: Resource<T, U, A>,
+    ) -> Rehydrated<ErasedFetch, T, U>
+    where
+        A::Hash: Clone + PartialEq + Send,
+        T: Send + 'static,
+        U: Send + 'static,
+        <Self as ResourceManager>::Fetch: Send + 'static,
+    {
+        let expected = resource.hash();
+
+        rehydrate::<_, T, U>(ResourceManager::fetch_verified(
+            self,
+            TypeId::of::<A>(),
+            Box::new(move || Box::new(resource.hash())),
+            Box::new(move |data| {
+                let mut hasher = H::new();
+                hasher.write(data);
+                hasher.hash() == expected
+            }),
+        ))
+    }
+
+    fn fetch_stream<A: Algorithm + Any, T, U: StreamingRehydrate<T>>(
+        &self,
+        resource: Resource<T, U, A>,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<T>, ResourceError<U::RehydrateError>>> + Send>>
+    where
+        A::Hash: Clone + Send,
+        T: Send + 'static,
+        U: Send + 'static,
+        <Self as ResourceManager>::Fetch: Send + 'static,
+        U::RehydrateStream: Send,
+        U::RehydrateError: Send,
+    {
+        let fetch = ResourceManager::fetch_stream(
+            self,
+            TypeId::of::<A>(),
+            Box::new(move || Box::new(resource.hash())),
+        );
+
+        Box::pin(async move {
+            let stream = match fetch.await.map_err(ResourceError::cast)? {
+                Some(stream) => stream,
+                None => return Ok(None),
+            };
+
+            decode_stream::<T, U, fn(&[u8])>(stream, None)
+                .await
+                .map(Some)
+        })
+    }
+
+    /// Like `fetch_stream`, but hashes the chunks with `H` as they arrive and fails with
+    /// `ResourceError::HashMismatch` unless the data matches `resource.hash()`.
+    fn fetch_stream_verified<
+        A: Algorithm + Any,
+        H: Hasher<A> + Send + 'static,
+        T,
+        U: StreamingRehydrate<T>,
+    >(
+        &self,
+        resource: Resource<T, U, A>,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<T>, ResourceError<U::RehydrateError>>> + Send>>
+    where
+        A::Hash: Clone + PartialEq + Send,
+        T: Send + 'static,
+        U: Send + 'static,
+        <Self as ResourceManager>::Fetch: Send + 'static,
+        U::RehydrateStream: Send,
+        U::RehydrateError: Send,
+    {
+        let expected = resource.hash();
+        let fetch = ResourceManager::fetch_stream(
+            self,
+            TypeId::of::<A>(),
+            Box::new(move || Box::new(resource.hash())),
+        );
+
+        Box::pin(async move {
+            let stream = match fetch.await.map_err(ResourceError::cast)? {
+                Some(stream) => stream,
+                None => return Ok(None),
+            };
+
+            let mut hasher = H::new();
+            let item =
+                decode_stream::<T, U, _>(stream, Some(|chunk: &[u8]| hasher.write(chunk))).await;
+
+            match item {
+                Err(ResourceError::Provider(e)) => Err(ResourceError::Provider(e)),
+                _ if hasher.hash() != expected => Err(ResourceError::HashMismatch),
+                item => item.map(Some),
+            }
+        })
     }
 }
 
@@ -163,3 +397,108 @@ pub type ErasedResourceRegistrant<A, E> = Box<
 
 pub type ErrorErasedResourceRegistrant<A> =
     ErasedResourceRegistrant<A, Box<dyn core_error::Error + Send>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        resource::provider::StreamingResourceProvider, Cbor, PortableSha256, Sha256, Sha256Sum,
+        SimpleResourceManager,
+    };
+    use futures::{executor::block_on, stream::iter};
+
+    struct Chunked(Vec<u8>);
+
+    impl StreamingResourceProvider<Sha256> for Chunked {
+        type FetchStream = Ready<Result<Option<ByteStream>, Infallible>>;
+
+        fn fetch_stream(&self, _: Sha256Sum) -> Self::FetchStream {
+            let chunks = self
+                .0
+                .chunks(7)
+                .map(|chunk| Ok(chunk.to_vec()))
+                .collect::<Vec<_>>();
+
+            ready(Ok(Some(Box::pin(iter(chunks)) as ByteStream)))
+        }
+    }
+
+    fn manager(data: Vec<u8>) -> SimpleResourceManager {
+        let mut manager = SimpleResourceManager::new();
+        block_on(manager.register_streaming_provider::<Sha256, _>(Chunked(data))).unwrap();
+        manager
+    }
+
+    fn resource(data: &[u8]) -> Resource<Vec<u32>, Cbor, Sha256> {
+        let mut hasher = PortableSha256::new();
+        hasher.write(data);
+        Resource::new(hasher.hash())
+    }
+
+    #[test]
+    fn streams_decode_across_chunks() {
+        let item = (0..10_000).collect::<Vec<u32>>();
+        let data = serde_cbor::to_vec(&item).unwrap();
+        let manager = manager(data.clone());
+
+        let fetched = block_on(ResourceManagerExt::fetch_stream(&manager, resource(&data)));
+        assert_eq!(fetched.unwrap(), Some(item.clone()));
+
+        let fetched =
+            block_on(manager.fetch_stream_verified::<_, PortableSha256, _, _>(resource(&data)));
+        assert_eq!(fetched.unwrap(), Some(item));
+    }
+
+    #[test]
+    fn streams_are_verified_while_decoding() {
+        let data = serde_cbor::to_vec(&vec![1u32, 2, 3]).unwrap();
+        let manager = manager(serde_cbor::to_vec(&vec![4u32]).unwrap());
+
+        assert!(matches!(
+            block_on(manager.fetch_stream_verified::<_, PortableSha256, _, _>(resource(&data))),
+            Err(ResourceError::HashMismatch)
+        ));
+    }
+
+    // implements only what `ResourceManager` requires
+    struct Whole(Vec<u8>);
+
+    impl ResourceManager for Whole {
+        type Fetch = Ready<Result<Option<Vec<u8>>, ResourceError<Infallible>>>;
+
+        fn fetch(
+            &self,
+            _: TypeId,
+            _: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        ) -> Self::Fetch {
+            ready(Ok(Some(self.0.clone())))
+        }
+    }
+
+    #[test]
+    fn streaming_and_verification_default_to_fetch() {
+        let data = serde_cbor::to_vec(&vec![1u32, 2, 3]).unwrap();
+        let manager = Whole(data.clone());
+
+        let fetched = block_on(ResourceManagerExt::fetch_stream(&manager, resource(&data)));
+        assert_eq!(fetched.unwrap(), Some(vec![1, 2, 3]));
+
+        let fetched = block_on(
+            ResourceManagerExt::fetch_verified::<_, PortableSha256, _, _>(
+                &manager,
+                resource(&data),
+            ),
+        );
+        assert_eq!(fetched.unwrap(), Some(vec![1, 2, 3]));
+
+        assert!(matches!(
+            block_on(
+                ResourceManagerExt::fetch_verified::<_, PortableSha256, _, _>(
+                    &manager,
+                    resource(&[])
+                )
+            ),
+            Err(ResourceError::HashMismatch)
+        ));
+    }
+}
@@ -1,12 +1,21 @@
-use core::{convert::Infallible, marker::PhantomData};
+use crate::ParseHashError;
+use core::{
+    convert::Infallible,
+    fmt::{self, Debug, Display, Formatter},
+    marker::PhantomData,
+    str::FromStr,
+};
 use core_error::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 
 mod rehydrate;
-pub use rehydrate::Rehydrate;
+pub use rehydrate::{Chunks, Rehydrate, StreamingRehydrate};
 pub mod hash;
-use hash::Algorithm;
+use hash::{Algorithm, Pair};
 pub mod manager;
+pub mod multihash;
+use multihash::{HashCode, MultihashError};
 pub mod provider;
 pub use manager::{ErasedResourceManager, ResourceManagerExt};
 
@@ -34,6 +43,74 @@ impl<T, U: Rehydrate<T>, A: Algorithm> Resource<T, U, A> {
     }
 }
 
+impl<T, U: Rehydrate<T>, A: Algorithm> Debug for Resource<T, U, A>
+where
+    A::Hash: Debug,
+{
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.debug_tuple("Resource").field(&self.0).finish()
+    }
+}
+
+/// Formats as a `vessel:<algorithm>:<hash>` URI, which `FromStr` parses back.
+impl<T, U: Rehydrate<T>, A: HashCode> Display for Resource<T, U, A>
+where
+    A::Hash: Display,
+{
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "vessel:{}:{}", A::NAME, self.0)
+    }
+}
+
+impl<T, U: Rehydrate<T>, A: HashCode> FromStr for Resource<T, U, A>
+where
+    A::Hash: FromStr<Err = ParseHashError>,
+{
+    type Err = ParseHashError;
+
+    fn from_str(uri: &str) -> Result<Self, ParseHashError> {
+        let uri = uri.strip_prefix("vessel:").ok_or(ParseHashError::Scheme)?;
+        let (algorithm, hash) = uri
+            .find(':')
+            .map(|i| (&uri[..i], &uri[i + 1..]))
+            .ok_or(ParseHashError::Scheme)?;
+
+        if algorithm != A::NAME {
+            return Err(ParseHashError::Algorithm {
+                expected: A::NAME,
+                found: algorithm.to_owned(),
+            });
+        }
+
+        Ok(Resource::new(hash.parse()?))
+    }
+}
+
+impl<T, U: Rehydrate<T>, A: Algorithm> Serialize for Resource<T, U, A>
+where
+    A::Hash: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T, U: Rehydrate<T>, A: Algorithm> Deserialize<'de> for Resource<T, U, A>
+where
+    A::Hash: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        A::Hash::deserialize(deserializer).map(Resource::new)
+    }
+}
+
+impl<T, U: Rehydrate<T>, A: Algorithm, B: Algorithm> Resource<T, U, Pair<A, B>> {
+    pub fn split(self) -> (Resource<T, U, A>, Resource<T, U, B>) {
+        let (a, b) = self.0;
+        (Resource::new(a), Resource::new(b))
+    }
+}
+
 #[derive(Debug, Error)]
 #[bounds(where T: Error + 'static)]
 pub enum ResourceError<T> {
@@ -43,6 +120,10 @@ pub enum ResourceError<T> {
     UnknownAlgorithm,
     #[error("rehydration error: {0}")]
     Rehydration(#[source] T),
+    #[error("provided data does not match the resource hash")]
+    HashMismatch,
+    #[error("multihash error: {0}")]
+    Multihash(#[source] MultihashError),
 }
 
 impl ResourceError<Infallible> {
@@ -51,6 +132,8 @@ impl ResourceError<Infallible> {
             ResourceError::Provider(e) => ResourceError::Provider(e),
             ResourceError::Rehydration(_) => panic!(),
             ResourceError::UnknownAlgorithm => ResourceError::UnknownAlgorithm,
+            ResourceError::HashMismatch => ResourceError::HashMismatch,
+            ResourceError::Multihash(e) => ResourceError::Multihash(e),
         }
     }
 }
@@ -60,3 +143,67 @@ impl<T> From<Box<dyn Error + Send>> for ResourceError<T> {
         ResourceError::Provider(input)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cbor, Sha256, Sha256Sum};
+
+    type Item = Resource<Vec<u32>, Cbor, Sha256>;
+
+    const ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn uris_round_trip() {
+        let resource = Item::new(ABC.parse().unwrap());
+        let uri = resource.to_string();
+
+        assert_eq!(uri, format!("vessel:sha256:{}", ABC));
+        assert_eq!(uri.parse::<Item>().unwrap().hash(), resource.hash());
+
+        let base32 = "vessel:sha256:xj4bnp4pahh6uqkbidpf3lrceoyagyndsylxvhfucd7wd4qacwwq";
+        assert_eq!(base32.parse::<Item>().unwrap().hash(), resource.hash());
+    }
+
+    #[test]
+    fn malformed_uris_are_rejected() {
+        assert_eq!(
+            format!("ipfs:sha256:{}", ABC).parse::<Item>().unwrap_err(),
+            ParseHashError::Scheme
+        );
+        assert_eq!(
+            format!("vessel:{}", ABC).parse::<Item>().unwrap_err(),
+            ParseHashError::Scheme
+        );
+        assert_eq!(
+            format!("vessel:sha512:{}", ABC)
+                .parse::<Item>()
+                .unwrap_err(),
+            ParseHashError::Algorithm {
+                expected: "sha256",
+                found: "sha512".to_owned()
+            }
+        );
+        assert_eq!(
+            format!("vessel:sha256:{}00", ABC)
+                .parse::<Item>()
+                .unwrap_err(),
+            ParseHashError::Length {
+                len: 66,
+                expected: 32
+            }
+        );
+    }
+
+    #[test]
+    fn resources_serialize_as_their_hash() {
+        let resource = Item::new(ABC.parse().unwrap());
+        let cbor = serde_cbor::to_vec(&resource).unwrap();
+
+        assert_eq!(cbor, serde_cbor::to_vec(&resource.hash()).unwrap());
+        assert_eq!(
+            serde_cbor::from_slice::<Item>(&cbor).unwrap().hash(),
+            ABC.parse::<Sha256Sum>().unwrap()
+        );
+    }
+}
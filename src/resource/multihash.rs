@@ -0,0 +1,398 @@
+use super::{hash::Algorithm, Rehydrate, Resource};
+use core::{
+    any::{Any, TypeId},
+    fmt::{self, Formatter},
+};
+use serde::{
+    de::{Error, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::collections::HashMap;
+
+/// An `Algorithm` with a stable multicodec code, whose hashes can be written as a `Multihash`.
+/// `NAME` is the short name used in `vessel:` URIs.
+pub trait HashCode: Algorithm {
+    const CODE: u64;
+    const NAME: &'static str;
+
+    fn to_digest(hash: &Self::Hash) -> Vec<u8>;
+    fn from_digest(digest: &[u8]) -> Option<Self::Hash>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MultihashError {
+    #[error("no algorithm registered for multihash code {0:#x}")]
+    UnknownCode(u64),
+    #[error("expected multihash code {expected:#x}, found {found:#x}")]
+    WrongAlgorithm { expected: u64, found: u64 },
+    #[error("digest of {len} bytes is not valid for multihash code {code:#x}")]
+    InvalidDigest { code: u64, len: usize },
+    #[error("multihash is truncated or malformed")]
+    Malformed,
+}
+
+/// Self-describing hash identifier: an algorithm code followed by the digest, encoded as in
+/// multihash with unsigned varints for the code and length.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Multihash {
+    code: u64,
+    digest: Vec<u8>,
+}
+
+fn write_varint(mut value: u64, buffer: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Result<u64, MultihashError> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = data.split_first().ok_or(MultihashError::Malformed)?;
+        *data = rest;
+        let bits = u64::from(byte & 0x7f);
+
+        // the tenth byte only has room for the most significant bit
+        if shift == 63 && bits > 1 {
+            return Err(MultihashError::Malformed);
+        }
+
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            // a trailing zero byte adds nothing, so the same value has a shorter encoding
+            if *byte == 0 && shift > 0 {
+                return Err(MultihashError::Malformed);
+            }
+
+            return Ok(value);
+        }
+    }
+
+    Err(MultihashError::Malformed)
+}
+
+impl Multihash {
+    pub fn new<A: HashCode>(hash: &A::Hash) -> Self {
+        Multihash {
+            code: A::CODE,
+            digest: A::to_digest(hash),
+        }
+    }
+
+    pub fn code(&self) -> u64 {
+        self.code
+    }
+
+    pub fn digest(&self) -> &[u8] {
+        &self.digest
+    }
+
+    pub fn hash<A: HashCode>(&self) -> Result<A::Hash, MultihashError> {
+        if self.code != A::CODE {
+            return Err(MultihashError::WrongAlgorithm {
+                expected: A::CODE,
+                found: self.code,
+            });
+        }
+
+        A::from_digest(&self.digest).ok_or(MultihashError::InvalidDigest {
+            code: self.code,
+            len: self.digest.len(),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        write_varint(self.code, &mut buffer);
+        write_varint(self.digest.len() as u64, &mut buffer);
+        buffer.extend_from_slice(&self.digest);
+        buffer
+    }
+
+    pub fn from_bytes(mut data: &[u8]) -> Result<Self, MultihashError> {
+        let code = read_varint(&mut data)?;
+        let len = read_varint(&mut data)? as usize;
+
+        if data.len() != len {
+            return Err(MultihashError::Malformed);
+        }
+
+        Ok(Multihash {
+            code,
+            digest: data.to_vec(),
+        })
+    }
+}
+
+impl Serialize for Multihash {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+struct MultihashVisitor;
+
+impl<'de> Visitor<'de> for MultihashVisitor {
+    type Value = Multihash;
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("a binary multihash")
+    }
+
+    fn visit_bytes<E: Error>(self, data: &[u8]) -> Result<Multihash, E> {
+        Multihash::from_bytes(data).map_err(E::custom)
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Multihash, S::Error> {
+        let mut data = vec![];
+
+        while let Some(byte) = seq.next_element()? {
+            data.push(byte);
+        }
+
+        self.visit_bytes(&data)
+    }
+}
+
+impl<'de> Deserialize<'de> for Multihash {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(MultihashVisitor)
+    }
+}
+
+impl<T, U: Rehydrate<T>, A: HashCode> Resource<T, U, A> {
+    pub fn multihash(&self) -> Multihash {
+        Multihash::new::<A>(&self.0)
+    }
+
+    pub fn from_multihash(multihash: &Multihash) -> Result<Self, MultihashError> {
+        multihash.hash::<A>().map(Resource::new)
+    }
+}
+
+struct Entry {
+    algo: TypeId,
+    decode: fn(&[u8]) -> Option<Box<dyn Any + Send>>,
+}
+
+fn decode<A: HashCode>(digest: &[u8]) -> Option<Box<dyn Any + Send>>
+where
+    A::Hash: Send + 'static,
+{
+    A::from_digest(digest).map(|hash| Box::new(hash) as Box<dyn Any + Send>)
+}
+
+/// Maps multihash codes back to the `Algorithm` types a `ResourceManager` keys providers by.
+pub struct Registry {
+    algorithms: HashMap<u64, Entry>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            algorithms: HashMap::new(),
+        }
+    }
+
+    pub fn register<A: HashCode + Any>(&mut self)
+    where
+        A::Hash: Send + 'static,
+    {
+        self.algorithms.insert(
+            A::CODE,
+            Entry {
+                algo: TypeId::of::<A>(),
+                decode: decode::<A>,
+            },
+        );
+    }
+
+    /// Produces the arguments `ResourceManager::fetch` and its siblings expect for `multihash`.
+    pub fn resolve(
+        &self,
+        multihash: &Multihash,
+    ) -> Result<(TypeId, Box<dyn FnMut() -> Box<dyn Any + Send> + Send>), MultihashError> {
+        let entry = self
+            .algorithms
+            .get(&multihash.code)
+            .ok_or(MultihashError::UnknownCode(multihash.code))?;
+
+        let invalid = MultihashError::InvalidDigest {
+            code: multihash.code,
+            len: multihash.digest.len(),
+        };
+
+        (entry.decode)(&multihash.digest).ok_or(invalid)?;
+
+        let decode = entry.decode;
+        let digest = multihash.digest.clone();
+
+        Ok((entry.algo, Box::new(move || decode(&digest).unwrap())))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register::<crate::Sha256>();
+        registry.register::<crate::Sha512>();
+        registry.register::<crate::Blake3>();
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        resource::{manager::ResourceManagerExt, ResourceError},
+        Cbor, MemoryStore, PortableSha256, Sha256, Sha256Sum, Sha512, Sha512Sum,
+        SimpleResourceManager,
+    };
+    use futures::executor::block_on;
+
+    type Item = Resource<Vec<u32>, Cbor, Sha256>;
+
+    fn abc() -> Sha256Sum {
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            .parse()
+            .unwrap()
+    }
+
+    fn varint(mut data: &[u8]) -> Result<u64, MultihashError> {
+        let value = read_varint(&mut data)?;
+        assert!(data.is_empty());
+        Ok(value)
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 0x7f, 0x80, 0x12, 0x1e, 0x3fff, 0x4000, u64::MAX].iter() {
+            let mut buffer = vec![];
+            write_varint(*value, &mut buffer);
+            assert_eq!(varint(&buffer).unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn overflowing_varints_are_rejected() {
+        let mut buffer = vec![0xff; 9];
+        buffer.push(0x02);
+
+        assert!(matches!(varint(&buffer), Err(MultihashError::Malformed)));
+
+        buffer[9] = 0x81;
+        buffer.push(0x01);
+
+        assert!(matches!(varint(&buffer), Err(MultihashError::Malformed)));
+    }
+
+    #[test]
+    fn non_canonical_varints_are_rejected() {
+        assert!(matches!(
+            varint(&[0x80, 0x00]),
+            Err(MultihashError::Malformed)
+        ));
+        assert!(matches!(
+            varint(&[0x81, 0x00]),
+            Err(MultihashError::Malformed)
+        ));
+        assert_eq!(varint(&[0x00]).unwrap(), 0);
+    }
+
+    #[test]
+    fn multihashes_round_trip_through_bytes() {
+        let multihash = Multihash::new::<Sha256>(&abc());
+        let bytes = multihash.to_bytes();
+
+        assert_eq!(&bytes[..2], &[0x12, 32]);
+        assert_eq!(&bytes[2..], &abc().0[..]);
+        assert_eq!(Multihash::from_bytes(&bytes).unwrap(), multihash);
+        assert!(matches!(
+            Multihash::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MultihashError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn resources_round_trip_through_multihashes() {
+        let resource = Item::new(abc());
+        let multihash = resource.multihash();
+
+        assert_eq!(multihash.code(), 0x12);
+        assert_eq!(Item::from_multihash(&multihash).unwrap().hash(), abc());
+
+        assert!(matches!(
+            Item::from_multihash(&Multihash::new::<Sha512>(&Sha512Sum([0; 64]))),
+            Err(MultihashError::WrongAlgorithm {
+                expected: 0x12,
+                found: 0x13
+            })
+        ));
+        assert!(matches!(
+            Item::from_multihash(&Multihash {
+                code: 0x12,
+                digest: vec![0; 31]
+            }),
+            Err(MultihashError::InvalidDigest { len: 31, .. })
+        ));
+    }
+
+    #[test]
+    fn registries_resolve_known_codes() {
+        let registry = Registry::default();
+
+        let (algo, mut hash) = registry.resolve(&Item::new(abc()).multihash()).unwrap();
+        assert_eq!(algo, TypeId::of::<Sha256>());
+        assert_eq!(*hash().downcast::<Sha256Sum>().unwrap(), abc());
+
+        assert!(matches!(
+            registry.resolve(&Multihash {
+                code: 0x99,
+                digest: vec![0; 32]
+            }),
+            Err(MultihashError::UnknownCode(0x99))
+        ));
+        assert!(matches!(
+            registry.resolve(&Multihash {
+                code: 0x12,
+                digest: vec![0; 31]
+            }),
+            Err(MultihashError::InvalidDigest {
+                code: 0x12,
+                len: 31
+            })
+        ));
+    }
+
+    #[test]
+    fn managers_fetch_by_multihash() {
+        let mut store = MemoryStore::<Sha256>::new();
+        let resource: Item = block_on(store.intern::<PortableSha256, _, _>(vec![1, 2, 3])).unwrap();
+
+        let mut manager = SimpleResourceManager::new();
+        block_on(manager.register_streaming_provider::<Sha256, _>(store)).unwrap();
+
+        let registry = Registry::default();
+        let fetched = block_on(manager.fetch_multihash(&registry, &resource.multihash()));
+
+        assert_eq!(
+            fetched.unwrap(),
+            Some(serde_cbor::to_vec(&vec![1u32, 2, 3]).unwrap())
+        );
+
+        let unknown = Multihash {
+            code: 0x99,
+            digest: vec![],
+        };
+
+        assert!(matches!(
+            block_on(manager.fetch_multihash(&registry, &unknown)),
+            Err(ResourceError::Multihash(MultihashError::UnknownCode(0x99)))
+        ));
+    }
+}
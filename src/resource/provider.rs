@@ -1,8 +1,14 @@
 use super::hash::Algorithm;
-use futures::{Future, TryFuture, TryFutureExt};
+use futures::{
+    io::{AsyncRead, AsyncReadExt},
+    stream::unfold,
+    Future, Stream, TryFuture, TryFutureExt,
+};
 use protocol::protocol;
 use std::{marker::PhantomData, pin::Pin};
 
+const CHUNK_SIZE: usize = 64 * 1024;
+
 #[protocol]
 pub trait ResourceProvider<A: Algorithm> {
     type Fetch: TryFuture<Ok = Option<Vec<u8>>>;
@@ -60,3 +66,30 @@ pub type ErasedResourceProvider<A, E> = Box<
 
 pub type ErrorErasedResourceProvider<A> =
     ErasedResourceProvider<A, Box<dyn core_error::Error + Send>>;
+
+pub type ByteStream =
+    Pin<Box<dyn Stream<Item = Result<Vec<u8>, Box<dyn core_error::Error + Send>>> + Send>>;
+
+/// Streaming counterpart to `ResourceProvider`, yielding the stored bytes in chunks rather than
+/// as a single buffer.
+pub trait StreamingResourceProvider<A: Algorithm> {
+    type FetchStream: TryFuture<Ok = Option<ByteStream>>;
+
+    fn fetch_stream(&self, hash: <A as Algorithm>::Hash) -> Self::FetchStream;
+}
+
+pub fn read_stream<R: AsyncRead + Unpin + Send + 'static>(reader: R) -> ByteStream {
+    Box::pin(unfold(Some(reader), |reader| async move {
+        let mut reader = reader?;
+        let mut buffer = vec![0u8; CHUNK_SIZE];
+
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(len) => {
+                buffer.truncate(len);
+                Some((Ok(buffer), Some(reader)))
+            }
+            Err(e) => Some((Err(Box::new(e) as Box<dyn core_error::Error + Send>), None)),
+        }
+    }))
+}
@@ -1,4 +1,5 @@
-use core::future::Future;
+use core::{future::Future, pin::Pin};
+use futures::Stream;
 
 pub trait Rehydrate<T>: Sized {
     type RehydrateError;
@@ -9,3 +10,17 @@ pub trait Rehydrate<T>: Sized {
     fn rehydrate(data: Vec<u8>) -> Self::Rehydrate;
     fn dump(data: T) -> Self::Dump;
 }
+
+pub type Chunks = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// Incremental counterpart to `Rehydrate` for resources too large to buffer. If the underlying
+/// provider fails partway through, `Chunks` simply ends and the manager reports the provider error
+/// in place of whatever `rehydrate_stream` resolves to. The serde codecs have no push decoder, so
+/// they collect the chunks before decoding; hashing still happens as the chunks arrive.
+pub trait StreamingRehydrate<T>: Rehydrate<T> {
+    type RehydrateStream: Future<Output = Result<T, Self::RehydrateError>>;
+    type DumpStream: Stream<Item = Result<Vec<u8>, Self::DumpError>>;
+
+    fn rehydrate_stream(data: Chunks) -> Self::RehydrateStream;
+    fn dump_stream(data: T) -> Self::DumpStream;
+}
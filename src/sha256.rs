@@ -0,0 +1,257 @@
+use crate::{resource::hash::Hasher, Sha256, Sha256Sum};
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Pure Rust `Hasher<Sha256>` with no dependencies, for targets `ring` does not support such as
+/// the wasm guest.
+#[derive(Clone)]
+pub struct PortableSha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    buffered: usize,
+    length: u64,
+}
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+
+    for (i, word) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+    }
+
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = *state;
+
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let t1 = h
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let t2 = s0.wrapping_add(maj);
+
+        h = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(t1);
+        d = c;
+        c = b;
+        b = a;
+        a = t1.wrapping_add(t2);
+    }
+
+    for (word, value) in state.iter_mut().zip(&[a, b, c, d, e, f, g, h]) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+impl Hasher<Sha256> for PortableSha256 {
+    fn new() -> Self {
+        PortableSha256 {
+            state: H,
+            block: [0u8; 64],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    fn write(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+
+        if self.buffered > 0 {
+            let len = data.len().min(64 - self.buffered);
+            self.block[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+
+            if self.buffered < 64 {
+                return;
+            }
+
+            compress(&mut self.state, &self.block);
+            self.buffered = 0;
+        }
+
+        let mut blocks = data.chunks_exact(64);
+
+        for block in &mut blocks {
+            compress(&mut self.state, block);
+        }
+
+        let remainder = blocks.remainder();
+        self.block[..remainder.len()].copy_from_slice(remainder);
+        self.buffered = remainder.len();
+    }
+
+    fn hash(&self) -> Sha256Sum {
+        let mut state = self.state;
+        let mut block = [0u8; 64];
+
+        block[..self.buffered].copy_from_slice(&self.block[..self.buffered]);
+        block[self.buffered] = 0x80;
+
+        if self.buffered >= 56 {
+            compress(&mut state, &block);
+            block = [0u8; 64];
+        }
+
+        block[56..].copy_from_slice(&self.length.wrapping_mul(8).to_be_bytes());
+        compress(&mut state, &block);
+
+        let mut sum = [0u8; 32];
+
+        for (bytes, word) in sum.chunks_exact_mut(4).zip(&state) {
+            bytes.copy_from_slice(&word.to_be_bytes());
+        }
+
+        Sha256Sum(sum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(data: &[u8]) -> Sha256Sum {
+        let mut hasher = PortableSha256::new();
+        hasher.write(data);
+        hasher.hash()
+    }
+
+    #[test]
+    fn fips_180_2_vectors() {
+        let vectors: [(&[u8], &str); 3] = [
+            (
+                b"",
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                b"abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+
+        for (data, expected) in vectors.iter() {
+            assert_eq!(digest(data), expected.parse().unwrap());
+        }
+
+        let mut hasher = PortableSha256::new();
+
+        for _ in 0..1000 {
+            hasher.write(&[b'a'; 1000]);
+        }
+
+        assert_eq!(
+            hasher.hash(),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn padding_boundaries() {
+        let vectors = [
+            (
+                55,
+                "9f4390f8d30c2dd92ec9f095b65e2b9ae9b0a925a5258e241c9f1e910f734318",
+            ),
+            (
+                56,
+                "b35439a4ac6f0948b6d6f9e3c6af0f5f590ce20f1bde7090ef7970686ec6738a",
+            ),
+            (
+                63,
+                "7d3e74a05d7db15bce4ad9ec0658ea98e3f06eeecf16b4c6fff2da457ddc2f34",
+            ),
+            (
+                64,
+                "ffe054fe7ae0cb6dc65c3af9b61d5209f439851db43d0ba5997337df154668eb",
+            ),
+            (
+                65,
+                "635361c48bb9eab14198e76ea8ab7f1a41685d6ad62aa9146d301d4f17eb0ae0",
+            ),
+        ];
+
+        for (len, expected) in vectors.iter() {
+            assert_eq!(digest(&vec![b'a'; *len]), expected.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn split_writes_match_a_single_write() {
+        let data = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = digest(&data);
+
+        for split in [1, 7, 55, 56, 63, 64, 65, 128, 299].iter() {
+            let mut hasher = PortableSha256::new();
+
+            for chunk in data.chunks(*split) {
+                hasher.write(chunk);
+            }
+
+            assert_eq!(hasher.hash(), expected, "chunks of {} bytes", split);
+        }
+
+        let mut hasher = PortableSha256::new();
+        hasher.write(&data[..10]);
+        hasher.write(&[]);
+        hasher.write(&data[10..]);
+        assert_eq!(hasher.hash(), expected);
+    }
+
+    #[cfg(feature = "ring-sha256")]
+    #[test]
+    fn matches_ring() {
+        use crate::Ring;
+
+        // deterministic xorshift input so that failures are reproducible
+        let mut seed = 0x2545f4914f6cdd1du64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..200 {
+            let len = (next() % 1024) as usize;
+            let data = (0..len).map(|_| next() as u8).collect::<Vec<_>>();
+
+            let mut ring = Ring::new();
+            ring.write(&data);
+
+            assert_eq!(digest(&data), ring.hash(), "{} byte input", len);
+        }
+    }
+}
@@ -1,12 +1,12 @@
 use crate::resource::{
     hash::Algorithm,
-    manager::{ResourceManager, ResourceRegistrant},
-    provider::ResourceProvider,
+    manager::{ErasedFetch, ErasedFetchStream, ResourceManager, ResourceRegistrant},
+    provider::{ByteStream, ResourceProvider, StreamingResourceProvider},
     ResourceError,
 };
 use core_error::Error;
 use futures::{
-    future::ready, lock::Mutex, stream::iter, Future, FutureExt, StreamExt, TryFuture, TryFutureExt,
+    future::ready, lock::Mutex, stream::once, Future, TryFuture, TryFutureExt, TryStreamExt,
 };
 use protocol::allocated::ProtocolError;
 use std::{
@@ -17,87 +17,99 @@ use std::{
     sync::Arc,
 };
 
+type Fetcher = Box<
+    dyn Fn(
+            Box<dyn Any + Send>,
+        ) -> Pin<
+            Box<dyn Future<Output = Result<Option<ByteStream>, Box<dyn Error + Send>>> + Send>,
+        > + Send,
+>;
+
 #[derive(Clone)]
 pub struct SimpleResourceManager {
-    providers: Arc<
-        Mutex<
-            HashMap<
-                TypeId,
-                Vec<
-                    Box<
-                        dyn Fn(
-                                Box<dyn Any + Send>,
-                            ) -> Pin<
-                                Box<
-                                    dyn Future<
-                                            Output = Result<Option<Vec<u8>>, Box<dyn Error + Send>>,
-                                        > + Send,
-                                >,
-                            > + Send,
-                    >,
-                >,
-            >,
-        >,
-    >,
+    providers: Arc<Mutex<HashMap<TypeId, Vec<Fetcher>>>>,
 }
 
 impl ResourceManager for SimpleResourceManager {
-    type Fetch =
-        Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, ResourceError<Infallible>>> + Send>>;
+    type Fetch = ErasedFetch;
 
     fn fetch(
         &self,
         algo: TypeId,
-        mut hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
     ) -> Self::Fetch {
+        self.fetch_verified(algo, hash, Box::new(|_| true))
+    }
+
+    fn fetch_stream(
+        &self,
+        algo: TypeId,
+        mut hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+    ) -> ErasedFetchStream {
         let providers = self.providers.clone();
 
-        Box::pin(
-            async move {
+        Box::pin(async move {
+            let futures = {
                 let providers = providers.lock().await;
 
-                let providers = providers
+                providers
                     .get(&algo)
-                    .ok_or(ResourceError::<Infallible>::UnknownAlgorithm)?;
+                    .ok_or(ResourceError::<Infallible>::UnknownAlgorithm)?
+                    .iter()
+                    .map(|provider| (provider)(hash()))
+                    .collect::<Vec<_>>()
+            };
+
+            for future in futures {
+                if let Some(stream) = future.await? {
+                    return Ok(Some(stream));
+                }
+            }
+
+            Ok(None)
+        })
+    }
+
+    fn fetch_verified(
+        &self,
+        algo: TypeId,
+        mut hash: Box<dyn FnMut() -> Box<dyn Any + Send> + Send>,
+        mut verify: Box<dyn FnMut(&[u8]) -> bool + Send>,
+    ) -> ErasedFetch {
+        let providers = self.providers.clone();
+
+        Box::pin(async move {
+            let futures = {
+                let providers = providers.lock().await;
 
-                let futures = providers
+                providers
+                    .get(&algo)
+                    .ok_or(ResourceError::<Infallible>::UnknownAlgorithm)?
                     .iter()
-                    .map(|provider| (provider)(hash()).into_stream())
-                    .collect::<Vec<_>>();
-
-                let futures = iter(futures.into_iter()).flatten();
-
-                Ok({
-                    let future: Pin<
-                        Box<
-                            dyn Future<
-                                    Output = Option<Result<Option<Vec<u8>>, Box<dyn Error + Send>>>,
-                                > + Send,
-                        >,
-                    > = Box::pin(
-                        futures
-                            .skip_while(|item| {
-                                ready(!(item.is_err() || item.as_ref().unwrap().is_some()))
-                            })
-                            .into_future()
-                            .map(|(data, _)| data),
-                    );
-                    future
-                })
+                    .map(|provider| (provider)(hash()))
+                    .collect::<Vec<_>>()
+            };
+
+            let mut mismatch = false;
+
+            for future in futures {
+                if let Some(stream) = future.await? {
+                    let data: Vec<u8> = stream.try_concat().await?;
+
+                    if verify(&data) {
+                        return Ok(Some(data));
+                    }
+
+                    mismatch = true;
+                }
             }
-            .then(
-                |item: Result<
-                    Pin<
-                        Box<
-                            dyn Future<
-                                    Output = Option<Result<Option<Vec<u8>>, Box<dyn Error + Send>>>,
-                                > + Send,
-                        >,
-                    >,
-                    ResourceError<Infallible>,
-                >| async { Ok(item?.await.transpose()?.flatten()) },
-            ),
-        )
+
+            if mismatch {
+                Err(ResourceError::HashMismatch)
+            } else {
+                Ok(None)
+            }
+        })
     }
 }
 
@@ -125,6 +137,9 @@ where
                     Box::pin(async move {
                         fut.into_future()
                             .await
+                            .map(|data| {
+                                data.map(|data| Box::pin(once(ready(Ok(data)))) as ByteStream)
+                            })
                             .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
                     })
                 }));
@@ -139,4 +154,132 @@ impl SimpleResourceManager {
             providers: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+
+    /// Registers a provider that yields its data in chunks. Such providers serve both
+    /// `ResourceManagerExt::fetch` and `ResourceManagerExt::fetch_stream`, as do those registered
+    /// through `ResourceRegistrant`.
+    pub fn register_streaming_provider<A, T>(
+        &mut self,
+        provider: T,
+    ) -> impl Future<Output = Result<(), ProtocolError>>
+    where
+        T: StreamingResourceProvider<A> + Send + 'static,
+        T::FetchStream: Unpin + Send + 'static,
+        A: Algorithm + Send + 'static,
+        <T::FetchStream as TryFuture>::Error: Error + Send,
+    {
+        let providers = self.providers.clone();
+
+        async move {
+            let mut providers = providers.lock().await;
+
+            providers
+                .entry(TypeId::of::<A>())
+                .or_insert(vec![])
+                .push(Box::new(move |any| {
+                    let fut = provider.fetch_stream(*Box::<dyn Any>::downcast(any).unwrap());
+
+                    Box::pin(async move {
+                        fut.into_future()
+                            .await
+                            .map_err(|e| Box::new(e) as Box<dyn Error + Send>)
+                    })
+                }));
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        resource::{hash::Hasher, manager::ResourceManagerExt},
+        Cbor, MemoryStore, PortableSha256, Resource, Sha256, Sha256Sum,
+    };
+    use futures::{executor::block_on, future::Ready};
+
+    struct Fixed(Vec<u8>);
+
+    impl StreamingResourceProvider<Sha256> for Fixed {
+        type FetchStream = Ready<Result<Option<ByteStream>, Infallible>>;
+
+        fn fetch_stream(&self, _: Sha256Sum) -> Self::FetchStream {
+            ready(Ok(Some(
+                Box::pin(once(ready(Ok(self.0.clone())))) as ByteStream
+            )))
+        }
+    }
+
+    fn manager(providers: Vec<Vec<u8>>) -> SimpleResourceManager {
+        let mut manager = SimpleResourceManager::new();
+
+        for data in providers {
+            block_on(manager.register_streaming_provider::<Sha256, _>(Fixed(data))).unwrap();
+        }
+
+        manager
+    }
+
+    fn encode(item: Vec<u32>) -> Vec<u8> {
+        serde_cbor::to_vec(&item).unwrap()
+    }
+
+    fn resource(data: &[u8]) -> Resource<Vec<u32>, Cbor, Sha256> {
+        let mut hasher = PortableSha256::new();
+        hasher.write(data);
+        Resource::new(hasher.hash())
+    }
+
+    #[test]
+    fn corrupt_data_falls_through_to_the_next_provider() {
+        let data = encode(vec![1, 2, 3]);
+        let manager = manager(vec![encode(vec![4]), data.clone()]);
+
+        let fetched = block_on(
+            ResourceManagerExt::fetch_verified::<_, PortableSha256, _, _>(
+                &manager,
+                resource(&data),
+            ),
+        );
+        assert_eq!(fetched.unwrap(), Some(vec![1, 2, 3]));
+
+        // unverified fetches trust the first provider
+        let fetched = block_on(ResourceManagerExt::fetch(&manager, resource(&data)));
+        assert_eq!(fetched.unwrap(), Some(vec![4]));
+    }
+
+    #[test]
+    fn only_corrupt_data_is_a_mismatch() {
+        let data = encode(vec![1, 2, 3]);
+        let manager = manager(vec![encode(vec![4]), encode(vec![5])]);
+
+        assert!(matches!(
+            block_on(
+                ResourceManagerExt::fetch_verified::<_, PortableSha256, _, _>(
+                    &manager,
+                    resource(&data)
+                )
+            ),
+            Err(ResourceError::HashMismatch)
+        ));
+    }
+
+    #[test]
+    fn missing_data_is_not_a_mismatch() {
+        let data = encode(vec![1, 2, 3]);
+        let mut manager = manager(vec![]);
+
+        block_on(manager.register_streaming_provider::<Sha256, _>(MemoryStore::<Sha256>::new()))
+            .unwrap();
+
+        let fetched = block_on(
+            ResourceManagerExt::fetch_verified::<_, PortableSha256, _, _>(
+                &manager,
+                resource(&data),
+            ),
+        );
+
+        assert_eq!(fetched.unwrap(), None);
+    }
 }